@@ -1,361 +1,456 @@
-use std::io;
+use std::{collections::HashSet, fmt, io, path::Path, str::FromStr, sync::OnceLock};
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tokio::{fs, process::Command};
+use tokio::fs;
 
-// https://github.com/dectalk/dectalk/blob/develop/src/Txt16bit/apndx_d.txt
+use crate::{preset_manager, utils::SplitMix64};
+
+mod chunk;
+mod code;
+pub mod markup;
+mod params;
+mod pool;
+mod sanitize;
+
+use markup::{Command, Node, Speaker};
+
+pub use chunk::split_sentences;
+pub use code::VoiceCodeError;
+pub use params::{VOICE_PARAMS, VoiceParam};
+pub use pool::{DECtalkPool, DECtalkPoolConfig};
+pub use sanitize::sanitize;
+
+/// A full set of `[:dv]` parameters, see [`VOICE_PARAMS`] for their ranges,
+/// units and descriptions.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DECtalkVoice {
-    pub sx: u8,  // 0    1    -- Sex 1 (male) or 0 (female)
-    pub hs: u8,  // 65   145  %  Head size
-    pub f4: u16, // 2000 4650 Hz Fourth formant frequency
-    pub f5: u16, // 2500 4950 Hz Fifth formant frequency
-    pub b4: u16, // 100  2048 Hz Fourth formant bandwidth
-    pub b5: u16, // 100  2048 Hz Fifth formant bandwidth
-    pub br: u8,  // 0    72   dB Breathiness
-    pub lx: u8,  // 0    100  %  Lax breathiness
-    pub sm: u8,  // 0    100  %  Smoothness (high frequency attenuation)
-    pub ri: u8,  // 0    100  %  Richness
-    pub nf: u8,  // 0    100  -- Number of fixed samplings of glottal pulse open phase
-    pub la: u8,  // 0    100  %  Laryngealization
-    pub bf: u8,  // 0    40   Hz Baseline fall
-    pub hr: u8,  // 2    100  Hz Hat rise
-    pub sr: u8,  // 1    100  Hz Stress rise
-    pub as_: u8, // 0    100  %  Assertiveness
-    pub qu: u8,  // 0    100  %  Quickness
-    pub ap: u16, // 50   350  Hz Average pitch
-    pub pr: u8,  // 0    250  %  Pitch range
-    pub gv: u8,  // 0    86   dB Gain of voicing source
-    pub gh: u8,  // 0    86   dB Gain of aspiration source
-    pub gf: u8,  // 0    86   dB Gain of frication source
-    pub gn: u8,  // 0    86   dB Gain of nasalization
-    pub g1: u8,  // 0    86   dB Gain of first formant resonator
-    pub g2: u8,  // 0    86   dB Gain of second formant resonator
-    pub g3: u8,  // 0    86   dB Gain of third formant resonator
-    pub g4: u8,  // 0    86   dB Gain of fourth formant resonator
-    pub g5: u8,  // 0    86   dB Gain of fifth formant resonator (replaces lo)
+    pub sx: u8,
+    pub hs: u8,
+    pub f4: u16,
+    pub f5: u16,
+    pub b4: u16,
+    pub b5: u16,
+    pub br: u8,
+    pub lx: u8,
+    pub sm: u8,
+    pub ri: u8,
+    pub nf: u8,
+    pub la: u8,
+    pub bf: u8,
+    pub hr: u8,
+    pub sr: u8,
+    pub as_: u8,
+    pub qu: u8,
+    pub ap: u16,
+    pub pr: u8,
+    pub gv: u8,
+    pub gh: u8,
+    pub gf: u8,
+    pub gn: u8,
+    pub g1: u8,
+    pub g2: u8,
+    pub g3: u8,
+    pub g4: u8,
+    pub g5: u8,
+}
+
+/// Speaking rate in words per minute, see `[:rate]`
+pub const DEFAULT_RATE: u16 = 180;
+pub const MIN_RATE: u16 = 75;
+pub const MAX_RATE: u16 = 600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    Us,
+    Uk,
+    Sp,
+    La,
+    Gr,
+    Fr,
+}
+
+impl Language {
+    pub const ALL: [Language; 6] = [
+        Language::Us,
+        Language::Uk,
+        Language::Sp,
+        Language::La,
+        Language::Gr,
+        Language::Fr,
+    ];
+
+    /// Code used by `[:lang]` and in the dictionary file names
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::Us => "us",
+            Language::Uk => "uk",
+            Language::Sp => "sp",
+            Language::La => "la",
+            Language::Gr => "gr",
+            Language::Fr => "fr",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Us => "US English",
+            Language::Uk => "UK English",
+            Language::Sp => "Castilian Spanish",
+            Language::La => "Latin American Spanish",
+            Language::Gr => "German",
+            Language::Fr => "French",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|language| language.code().eq_ignore_ascii_case(code))
+    }
+}
+
+/// Languages whose dictionary (`dtalk_<code>.dic`) sits next to the `say`
+/// binary. US English is always there, it is what `say` falls back to.
+pub async fn installed_languages(say_path: &Path) -> Vec<Language> {
+    let dir = say_path.parent().unwrap_or(Path::new("."));
+
+    let mut languages = vec![Language::Us];
+    for language in Language::ALL.into_iter().skip(1) {
+        let dictionary = dir.join(format!("dtalk_{}.dic", language.code()));
+        if fs::metadata(&dictionary).await.is_ok() {
+            languages.push(language);
+        }
+    }
+    languages
 }
 
-pub const PAUL_VOICE: DECtalkVoice = DECtalkVoice {
-    sx: 1,
-    hs: 100,
-    f4: 3300,
-    f5: 3650,
-    b4: 260,
-    b5: 330,
-    br: 0,
-    lx: 0,
-    sm: 3,
-    ri: 70,
-    nf: 0,
-    la: 0,
-    bf: 18,
-    hr: 18,
-    sr: 32,
-    as_: 100,
-    qu: 40,
-    ap: 122,
-    pr: 100,
-    gv: 65,
-    gh: 70,
-    gn: 74,
-    gf: 70,
-    g1: 68,
-    g2: 60,
-    g3: 48,
-    g4: 64,
-    g5: 86,
-};
-
-pub const HARRY_VOICE: DECtalkVoice = DECtalkVoice {
-    sx: 1,
-    hs: 115,
-    f4: 3300,
-    f5: 3850,
-    b4: 200,
-    b5: 240,
-    br: 0,
-    lx: 0,
-    sm: 12,
-    ri: 86,
-    nf: 10,
-    la: 0,
-    bf: 9,
-    hr: 20,
-    sr: 30,
-    as_: 100,
-    qu: 10,
-    ap: 89,
-    pr: 80,
-    gv: 65,
-    gh: 70,
-    gn: 73,
-    gf: 70,
-    g1: 71,
-    g2: 60,
-    g3: 52,
-    g4: 64,
-    g5: 81,
-};
-
-pub const FRANK_VOICE: DECtalkVoice = DECtalkVoice {
-    sx: 1,
-    hs: 90,
-    f4: 3650,
-    f5: 4200,
-    b4: 280,
-    b5: 300,
-    br: 50,
-    lx: 50,
-    sm: 46,
-    ri: 40,
-    nf: 0,
-    la: 5,
-    bf: 9,
-    hr: 20,
-    sr: 22,
-    as_: 65,
-    qu: 0,
-    ap: 155,
-    pr: 90,
-    gv: 63,
-    gh: 68,
-    gn: 75,
-    gf: 68,
-    g1: 63,
-    g2: 58,
-    g3: 56,
-    g4: 66,
-    g5: 86,
-};
-
-pub const DENNIS_VOICE: DECtalkVoice = DECtalkVoice {
-    sx: 1,
-    hs: 105,
-    f4: 3200,
-    f5: 3600,
-    b4: 240,
-    b5: 280,
-    br: 38,
-    lx: 70,
-    sm: 100,
-    ri: 0,
-    nf: 10,
-    la: 0,
-    bf: 9,
-    hr: 20,
-    sr: 22,
-    as_: 100,
-    qu: 50,
-    ap: 110,
-    pr: 135,
-    gv: 63,
-    gh: 68,
-    gn: 76,
-    gf: 68,
-    g1: 75,
-    g2: 60,
-    g3: 52,
-    g4: 61,
-    g5: 84,
-};
-
-pub const BETTY_VOICE: DECtalkVoice = DECtalkVoice {
-    sx: 0,
-    hs: 100,
-    f4: 4450,
-    f5: 2500,
-    b4: 260,
-    b5: 2048,
-    br: 0,
-    lx: 80,
-    sm: 4,
-    ri: 40,
-    nf: 0,
-    la: 0,
-    bf: 0,
-    hr: 14,
-    sr: 20,
-    as_: 35,
-    qu: 55,
-    ap: 208,
-    pr: 140,
-    gv: 65,
-    gh: 70,
-    gn: 72,
-    gf: 72,
-    g1: 69,
-    g2: 65,
-    g3: 50,
-    g4: 56,
-    g5: 81,
-};
-
-pub const URSULA_VOICE: DECtalkVoice = DECtalkVoice {
-    sx: 0,
-    hs: 95,
-    f4: 4500,
-    f5: 2500,
-    b4: 230,
-    b5: 2048,
-    br: 0,
-    lx: 50,
-    sm: 60,
-    ri: 100,
-    nf: 10,
-    la: 0,
-    bf: 8,
-    hr: 20,
-    sr: 32,
-    as_: 100,
-    qu: 30,
-    ap: 240,
-    pr: 135,
-    gv: 65,
-    gh: 70,
-    gn: 74,
-    gf: 70,
-    g1: 67,
-    g2: 65,
-    g3: 51,
-    g4: 58,
-    g5: 80,
-};
-
-pub const WENDY_VOICE: DECtalkVoice = DECtalkVoice {
-    sx: 0,
-    hs: 100,
-    f4: 4500,
-    f5: 2500,
-    b4: 400,
-    b5: 2048,
-    br: 55,
-    lx: 80,
-    sm: 100,
-    ri: 0,
-    nf: 10,
-    la: 0,
-    bf: 0,
-    hr: 20,
-    sr: 22,
-    as_: 50,
-    qu: 10,
-    ap: 200,
-    pr: 175,
-    gv: 51,
-    gh: 68,
-    gn: 75,
-    gf: 70,
-    g1: 69,
-    g2: 62,
-    g3: 53,
-    g4: 55,
-    g5: 83,
-};
-
-pub const RITA_VOICE: DECtalkVoice = DECtalkVoice {
-    sx: 0,
-    hs: 95,
-    f4: 4000,
-    f5: 2500,
-    b4: 250,
-    b5: 2048,
-    br: 46,
-    lx: 0,
-    sm: 24,
-    ri: 20,
-    nf: 0,
-    la: 4,
-    bf: 0,
-    hr: 20,
-    sr: 32,
-    as_: 65,
-    qu: 30,
-    ap: 106,
-    pr: 80,
-    gv: 65,
-    gh: 70,
-    gn: 73,
-    gf: 72,
-    g1: 69,
-    g2: 72,
-    g3: 48,
-    g4: 54,
-    g5: 83,
-};
-
-pub const KIT_VOICE: DECtalkVoice = DECtalkVoice {
-    sx: 0,
-    hs: 80,
-    f4: 2500,
-    f5: 2500,
-    b4: 2048,
-    b5: 2048,
-    br: 47,
-    lx: 75,
-    sm: 5,
-    ri: 40,
-    nf: 0,
-    la: 0,
-    bf: 0,
-    hr: 20,
-    sr: 22,
-    as_: 65,
-    qu: 50,
-    ap: 306,
-    pr: 210,
-    gv: 65,
-    gh: 70,
-    gn: 71,
-    gf: 72,
-    g1: 69,
-    g2: 69,
-    g3: 52,
-    g4: 50,
-    g5: 73,
-};
+/// How a message is spoken, everything besides the text itself
+#[derive(Debug, Clone)]
+pub struct DECtalkSettings {
+    pub voice: DECtalkVoice,
+    pub rate: u16,
+    pub language: Language,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoiceFieldError {
+    pub field: &'static str,
+    pub value: u16,
+    pub min: u16,
+    pub max: u16,
+    pub unit: &'static str,
+}
+
+impl fmt::Display for VoiceFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` is {}, must be {}-{}",
+            self.field, self.value, self.min, self.max
+        )?;
+        if !self.unit.is_empty() {
+            write!(f, " {}", self.unit)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for VoiceFieldError {}
 
 fn range_validate<Num: PartialOrd>(value: Num, min: Num, max: Num) -> bool {
     value >= min && value <= max
 }
 
 impl DECtalkVoice {
-    pub fn validate(&self) -> bool {
-        range_validate(self.sx, 0, 1)
-            && range_validate(self.hs, 65, 145)
-            && range_validate(self.f4, 2000, 4650)
-            && range_validate(self.f5, 2500, 4950)
-            && range_validate(self.b4, 100, 2048)
-            && range_validate(self.b5, 100, 2048)
-            && range_validate(self.br, 0, 72)
-            && range_validate(self.lx, 0, 100)
-            && range_validate(self.sm, 0, 100)
-            && range_validate(self.ri, 0, 100)
-            && range_validate(self.nf, 0, 100)
-            && range_validate(self.la, 0, 100)
-            && range_validate(self.bf, 0, 40)
-            && range_validate(self.hr, 2, 100)
-            && range_validate(self.sr, 1, 100)
-            && range_validate(self.as_, 0, 100)
-            && range_validate(self.qu, 0, 100)
-            && range_validate(self.ap, 50, 350)
-            && range_validate(self.pr, 0, 250)
-            && range_validate(self.gv, 0, 86)
-            && range_validate(self.gh, 0, 86)
-            && range_validate(self.gf, 0, 86)
-            && range_validate(self.gn, 0, 86)
-            && range_validate(self.g1, 0, 86)
-            && range_validate(self.g2, 0, 86)
-            && range_validate(self.g3, 0, 86)
-            && range_validate(self.g4, 0, 86)
-            && range_validate(self.g5, 0, 86)
+    /// The `[:dv ...]` command setting every parameter of this voice
+    pub fn design_command(&self) -> Command {
+        Command::DesignVoice(
+            VOICE_PARAMS
+                .iter()
+                .map(|param| (param.code.to_string(), param.get(self).into()))
+                .collect(),
+        )
+    }
+
+    /// Sets the parameter with the given `[:dv]` code, without validating it
+    pub fn set(&mut self, code: &str, value: u32) -> Result<(), ParseVoiceError> {
+        let param = VoiceParam::find(code)
+            .ok_or_else(|| ParseVoiceError::UnknownParameter(code.to_string()))?;
+        param.set(self, value)
+    }
+
+    /// Interpolates every parameter from `a` (`t` = 0) to `b` (`t` = 1)
+    pub fn lerp(a: &DECtalkVoice, b: &DECtalkVoice, t: f32) -> DECtalkVoice {
+        Self::blend(&[(a, 1.0 - t), (b, t)])
+    }
+
+    /// Weighted average of voices, rounded and clamped to the valid ranges.
+    /// Weights don't need to add up to 1, Paul is returned when they add up
+    /// to 0.
+    pub fn blend(voices: &[(&DECtalkVoice, f32)]) -> DECtalkVoice {
+        let total: f32 = voices.iter().map(|(_, weight)| weight).sum();
+        let mut blended = DECtalkVoice::default();
+        if total == 0.0 {
+            return blended;
+        }
+
+        for param in &VOICE_PARAMS {
+            let value: f32 = voices
+                .iter()
+                .map(|(voice, weight)| f32::from(param.get(voice)) * weight)
+                .sum::<f32>()
+                / total;
+            let value = value
+                .round()
+                .clamp(f32::from(param.min), f32::from(param.max));
+            // Always fits, the range is within the field's type.
+            param.set(&mut blended, value as u32).ok();
+        }
+        blended
+    }
+
+    /// How far apart two voices are. Each parameter's difference is divided
+    /// by the width of its range, so 0 is identical and 1 is as far apart as
+    /// a single parameter can be; the result is the root mean square of those.
+    pub fn distance(&self, other: &DECtalkVoice) -> f32 {
+        let sum: f32 = self
+            .differences(other)
+            .iter()
+            .map(|(_, difference)| difference * difference)
+            .sum();
+        (sum / VOICE_PARAMS.len() as f32).sqrt()
+    }
+
+    /// Range-normalized difference of every parameter, `other` minus `self`,
+    /// biggest first
+    pub fn differences(&self, other: &DECtalkVoice) -> Vec<(&'static VoiceParam, f32)> {
+        let mut differences: Vec<_> = VOICE_PARAMS
+            .iter()
+            .map(|param| {
+                let difference = f32::from(param.get(other)) - f32::from(param.get(self));
+                (param, difference / f32::from(param.max - param.min))
+            })
+            .collect();
+        differences.sort_by(|(_, a), (_, b)| b.abs().total_cmp(&a.abs()));
+        differences
+    }
+
+    /// Checks every parameter against its range, listing all that are out of it
+    pub fn validate(&self) -> Result<(), Vec<VoiceFieldError>> {
+        let errors: Vec<_> = VOICE_PARAMS
+            .iter()
+            .filter(|param| !range_validate(param.get(self), param.min, param.max))
+            .map(|param| VoiceFieldError {
+                field: param.code,
+                value: param.get(self),
+                min: param.min,
+                max: param.max,
+                unit: param.unit,
+            })
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
 }
 
 impl Default for DECtalkVoice {
     fn default() -> Self {
-        PAUL_VOICE
+        default_voice().clone()
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ParseVoiceError {
+    #[error("Unknown parameter `{0}`")]
+    UnknownParameter(String),
+    #[error("Value `{1}` doesn't fit in `{0}`")]
+    InvalidValue(String, u32),
+    #[error("Unexpected `{0}`, only `[:nX]` and `[:dv ...]` are allowed")]
+    Unexpected(String),
+    #[error("Missing parameter `{0}`")]
+    MissingParameter(String),
+}
+
+/// The built-in voice selected by `[:nX]`, `None` for Val, the voice being
+/// designed, which keeps whatever it was set to.
+pub fn speaker_voice(speaker: Speaker) -> Option<&'static DECtalkVoice> {
+    preset_manager::builtin_presets()
+        .iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(speaker.name()))
+        .map(|preset| &preset.voice)
+}
+
+/// Paul, the voice of anyone who hasn't set one
+pub fn default_voice() -> &'static DECtalkVoice {
+    speaker_voice(Speaker::Paul).expect("Paul is a built-in preset")
+}
+
+/// A voice derived from `seed`: a built-in voice picked by it, with pitch,
+/// head size and upper formants shifted within their valid ranges. The same
+/// seed always gives the same voice.
+pub fn seeded_voice(seed: u64) -> DECtalkVoice {
+    let mut rng = SplitMix64::new(seed);
+
+    let presets = builtin_voices();
+    let voice = presets[rng.range(0, presets.len() as i64 - 1) as usize].clone();
+    jitter(voice, &mut rng)
+}
+
+/// A random voice somewhere between two built-in voices, shifted the same
+/// way as [`seeded_voice`].
+pub fn natural_voice(rng: &mut SplitMix64) -> DECtalkVoice {
+    let presets = builtin_voices();
+    let a = &presets[rng.range(0, presets.len() as i64 - 1) as usize];
+    let b = &presets[rng.range(0, presets.len() as i64 - 1) as usize];
+    let voice = DECtalkVoice::lerp(a, b, rng.range(0, 100) as f32 / 100.0);
+    jitter(voice, rng)
+}
+
+/// A random voice with every parameter anywhere in its range. Expect odd
+/// results.
+pub fn wild_voice(rng: &mut SplitMix64) -> DECtalkVoice {
+    let mut voice = DECtalkVoice::default();
+    for param in &VOICE_PARAMS {
+        let value = rng.range(param.min.into(), param.max.into());
+        param.set(&mut voice, value as u32).ok();
+    }
+    voice
+}
+
+fn builtin_voices() -> Vec<DECtalkVoice> {
+    preset_manager::builtin_presets()
+        .iter()
+        .map(|preset| preset.voice.clone())
+        .collect()
+}
+
+// Shifts pitch, head size and upper formants a little, staying in range.
+fn jitter(mut voice: DECtalkVoice, rng: &mut SplitMix64) -> DECtalkVoice {
+    let pitch = i64::from(voice.ap) * rng.range(-12, 12) / 100;
+    let head_size = rng.range(-6, 6);
+    let formants = rng.range(-150, 150);
+
+    let mut shift = |code: &str, offset: i64| {
+        if let Some(param) = VoiceParam::find(code) {
+            let value = (i64::from(param.get(&voice)) + offset)
+                .clamp(i64::from(param.min), i64::from(param.max));
+            param.set(&mut voice, value as u32).ok();
+        }
+    };
+
+    shift("ap", pitch);
+    shift("hs", head_size);
+    // F4 and F5 shift by the same amount to keep the gap between them, each
+    // clamped to its own range (female voices already sit at the F5 minimum).
+    shift("f4", formants);
+    shift("f5", formants);
+
+    voice
+}
+
+impl DECtalkVoice {
+    /// Renders the voice the way it is shared, `[:nv][:dv sx 1 hs 100 ...]`
+    pub fn to_dectalk_string(&self) -> String {
+        markup::serialize(&[
+            Node::Command(Command::Name(Speaker::Val)),
+            Node::Command(self.design_command()),
+        ])
+    }
+
+    /// Applies `[:nX]` and `[:dv ...]` commands on top of this voice, so
+    /// `[:dv ap 90 pr 200]` only changes pitch. The result isn't validated.
+    pub fn apply_dectalk_str(&mut self, s: &str) -> Result<(), ParseVoiceError> {
+        for node in markup::parse(s) {
+            match node {
+                Node::Command(Command::Name(speaker)) => {
+                    if let Some(voice) = speaker_voice(speaker) {
+                        *self = voice.clone();
+                    }
+                }
+                Node::Command(Command::DesignVoice(params)) => {
+                    for (param, value) in params {
+                        self.set(&param, value)?;
+                    }
+                }
+                Node::Text(text) if text.trim().is_empty() => {}
+                node => return Err(ParseVoiceError::Unexpected(node.to_string())),
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses a `[:dv ...]` command setting every parameter, the way the
+    /// built-in voices are written. Unlike [`str::parse`], nothing is taken
+    /// from Paul.
+    pub fn from_design(s: &str) -> Result<Self, ParseVoiceError> {
+        let mut voice = DECtalkVoice {
+            sx: 0,
+            hs: 0,
+            f4: 0,
+            f5: 0,
+            b4: 0,
+            b5: 0,
+            br: 0,
+            lx: 0,
+            sm: 0,
+            ri: 0,
+            nf: 0,
+            la: 0,
+            bf: 0,
+            hr: 0,
+            sr: 0,
+            as_: 0,
+            qu: 0,
+            ap: 0,
+            pr: 0,
+            gv: 0,
+            gh: 0,
+            gf: 0,
+            gn: 0,
+            g1: 0,
+            g2: 0,
+            g3: 0,
+            g4: 0,
+            g5: 0,
+        };
+        let mut set = HashSet::new();
+        for node in markup::parse(s) {
+            match node {
+                Node::Command(Command::DesignVoice(params)) => {
+                    for (code, value) in params {
+                        let param = VoiceParam::find(&code)
+                            .ok_or(ParseVoiceError::UnknownParameter(code))?;
+                        param.set(&mut voice, value)?;
+                        set.insert(param.code);
+                    }
+                }
+                Node::Text(text) if text.trim().is_empty() => {}
+                node => return Err(ParseVoiceError::Unexpected(node.to_string())),
+            }
+        }
+
+        match VOICE_PARAMS.iter().find(|param| !set.contains(param.code)) {
+            Some(param) => Err(ParseVoiceError::MissingParameter(param.code.to_string())),
+            None => Ok(voice),
+        }
+    }
+}
+
+/// Parses `[:nX]` and `[:dv ...]` commands, starting from Paul.
+impl FromStr for DECtalkVoice {
+    type Err = ParseVoiceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut voice = DECtalkVoice::default();
+        voice.apply_dectalk_str(s)?;
+        Ok(voice)
     }
 }
 
@@ -367,65 +462,45 @@ pub enum DECtalkError {
     IOError(#[from] io::Error),
     #[error("Say error: {0}")]
     SayError(String),
+    #[error("Synthesis timed out")]
+    Timeout,
+    #[error("Output too large: {0} bytes")]
+    OutputTooLarge(u64),
 }
 
-pub async fn tts(text: &str, voice: &DECtalkVoice, id: u64) -> Result<Vec<u8>, DECtalkError> {
-    if !text.is_ascii() {
-        return Err(DECtalkError::InvalidASCII);
+static POOL: OnceLock<DECtalkPool> = OnceLock::new();
+
+/// Starts the synthesis workers, must be called before the first [`tts`].
+pub fn init_pool(config: DECtalkPoolConfig) {
+    // Only build a pool when there is none yet, building one clears the
+    // directories of the running pool's workers as stale.
+    let mut config = Some(config);
+    POOL.get_or_init(|| DECtalkPool::new(config.take().expect("initialized once")));
+    if config.is_some() {
+        log::warn!("DECtalk pool already initialized");
     }
+}
 
-    let filename = format!("dectalk/{}.wav", id);
-    let mut cmd = Command::new("dectalk/say");
-    cmd.arg("-fo").arg(&filename);
-    cmd.arg("-pre").arg(format!(
-        "[:phoneme on][:nv]
-        [:dv sx {}][:dv hs {}][:dv f4 {}][:dv f5 {}][:dv b4 {}][:dv b5 {}]
-        [:dv br {}][:dv lx {}][:dv sm {}][:dv ri {}][:dv nf {}][:dv la {}]
-        [:dv bf {}][:dv hr {}][:dv sr {}][:dv as {}][:dv qu {}][:dv ap {}][:dv pr {}]
-        [:dv gv {}][:dv gh {}][:dv gf {}][:dv gn {}][:dv g1 {}][:dv g2 {}][:dv g3 {}][:dv g4 {}][:dv g5 {}]",
-        voice.sx,
-        voice.hs,
-        voice.f4,
-        voice.f5,
-        voice.b4,
-        voice.b5,
-        voice.br,
-        voice.lx,
-        voice.sm,
-        voice.ri,
-        voice.nf,
-        voice.la,
-        voice.bf,
-        voice.hr,
-        voice.sr,
-        voice.as_,
-        voice.qu,
-        voice.ap,
-        voice.pr,
-        voice.gv,
-        voice.gh,
-        voice.gf,
-        voice.gn,
-        voice.g1,
-        voice.g2,
-        voice.g3,
-        voice.g4,
-        voice.g5
-    ));
-    cmd.arg("-a").arg(text);
-
-    let output = cmd.output().await?;
-    if !output.status.success() {
-        if fs::metadata(&filename).await.is_ok() {
-            fs::remove_file(&filename).await?;
-        }
+fn preamble(settings: &DECtalkSettings) -> String {
+    markup::serialize(&[
+        // Switching language resets the voice, so it has to come first.
+        Node::Command(Command::Lang(settings.language.code().to_string())),
+        Node::Command(Command::Phoneme(vec!["on".to_string()])),
+        Node::Command(Command::Name(Speaker::Val)),
+        Node::Command(settings.voice.design_command()),
+        Node::Command(Command::Rate(settings.rate.into())),
+    ])
+}
 
-        return Err(DECtalkError::SayError(
-            String::from_utf8_lossy(&output.stderr).to_string(),
-        ));
+pub async fn tts(text: &str, settings: &DECtalkSettings, id: u64) -> Result<Vec<u8>, DECtalkError> {
+    if !text.is_ascii() {
+        return Err(DECtalkError::InvalidASCII);
     }
 
-    let bytes = fs::read(&filename).await?;
-    fs::remove_file(&filename).await?;
-    Ok(bytes)
+    let pool = POOL.get_or_init(|| DECtalkPool::new(DECtalkPoolConfig::default()));
+    let input = format!("{}\n{}", preamble(settings), text);
+
+    pool.synthesize(&input).await.inspect_err(|err| {
+        log::debug!("Synthesis {} failed: {}", id, err);
+    })
 }
@@ -0,0 +1,103 @@
+use super::markup::{self, Command, Node};
+
+// Sentences longer than this are also split after commas and colons.
+const CLAUSE_LEN: usize = 120;
+
+/// Splits DECtalk text into sentences (or clauses, for long sentences) that
+/// can be synthesized one by one.
+///
+/// Markup is never split. Each chunk starts with the commands from earlier
+/// chunks that change how the rest is spoken (`[:rate]`, `[:dv]`, `[:nX]`,
+/// ...), so the voice stays the same across chunks.
+pub fn split_sentences(text: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut state: Vec<Command> = Vec::new();
+    let mut current: Vec<Node> = Vec::new();
+    let mut current_len = 0;
+
+    for node in markup::parse(text) {
+        let Node::Text(text) = node else {
+            if let Node::Command(command) = &node {
+                apply_state(&mut state, command);
+            }
+            current_len += node.to_string().len();
+            current.push(node);
+            continue;
+        };
+
+        let mut rest = text.as_str();
+        while let Some(end) = find_break(rest, current_len) {
+            current.push(Node::Text(rest[..end].to_string()));
+            push_chunk(&mut chunks, &mut current);
+            current.extend(state.iter().cloned().map(Node::Command));
+            current_len = 0;
+            rest = &rest[end..];
+        }
+
+        current_len += rest.len();
+        current.push(Node::Text(rest.to_string()));
+    }
+
+    push_chunk(&mut chunks, &mut current);
+    chunks
+}
+
+// Byte offset just past the next sentence break in `text`, if any.
+fn find_break(text: &str, current_len: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    (0..bytes.len()).find_map(|i| {
+        let clause = current_len + i >= CLAUSE_LEN;
+        let breaks = match bytes[i] {
+            b'.' | b'!' | b'?' | b';' | b'\n' => true,
+            b',' | b':' => clause,
+            _ => false,
+        };
+        let followed_by_space = bytes.get(i + 1).is_none_or(|c| c.is_ascii_whitespace());
+        // "e.g. this" is not the end of a sentence.
+        let abbreviation = bytes[i] == b'.'
+            && text[i + 1..]
+                .trim_start()
+                .starts_with(|c: char| c.is_ascii_lowercase());
+
+        (breaks && followed_by_space && !abbreviation && i + 1 < bytes.len()).then_some(i + 1)
+    })
+}
+
+fn push_chunk(chunks: &mut Vec<String>, current: &mut Vec<Node>) {
+    let spoken = current.iter().any(|node| match node {
+        Node::Text(text) => !text.trim().is_empty(),
+        Node::Phonemes(_) => true,
+        Node::Command(command) => {
+            matches!(command, Command::Tone { .. } | Command::Dial(_))
+        }
+    });
+
+    if spoken {
+        chunks.push(markup::serialize(current).trim().to_string());
+    }
+    current.clear();
+}
+
+// Remembers commands whose effect outlasts the chunk they appear in,
+// dropping earlier ones they override.
+fn apply_state(state: &mut Vec<Command>, command: &Command) {
+    match command {
+        Command::Name(_) => {
+            state.retain(|c| !matches!(c, Command::Name(_) | Command::DesignVoice(_)));
+        }
+        Command::Mode { mode, .. } => {
+            state.retain(|c| !matches!(c, Command::Mode { mode: m, .. } if m == mode));
+        }
+        Command::DesignVoice(_) | Command::Volume(_) => {}
+        Command::Rate(_)
+        | Command::Phoneme(_)
+        | Command::Comma(_)
+        | Command::Period(_)
+        | Command::Lang(_) => {
+            state.retain(|c| c.name() != command.name());
+        }
+        Command::Tone { .. } | Command::Dial(_) | Command::Other { .. } => return,
+    }
+
+    state.push(command.clone());
+}
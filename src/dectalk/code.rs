@@ -0,0 +1,200 @@
+use thiserror::Error;
+
+use super::{DECtalkVoice, VoiceFieldError, VoiceParam, params::ParamKind};
+
+// Layout of a voice code, before base64: the version, every field in
+// `V1_FIELDS` order (one byte for u8 fields, two big endian bytes for u16
+// ones), then a Fletcher-16 checksum of everything before it.
+const VERSION: u8 = 1;
+
+// Written out rather than taken from `VOICE_PARAMS`, so reordering or adding
+// parameters there doesn't change what existing codes decode to.
+#[rustfmt::skip]
+const V1_FIELDS: [(&str, ParamKind); 28] = [
+    ("sx", ParamKind::U8), ("hs", ParamKind::U8), ("f4", ParamKind::U16), ("f5", ParamKind::U16),
+    ("b4", ParamKind::U16), ("b5", ParamKind::U16), ("br", ParamKind::U8), ("lx", ParamKind::U8),
+    ("sm", ParamKind::U8), ("ri", ParamKind::U8), ("nf", ParamKind::U8), ("la", ParamKind::U8),
+    ("bf", ParamKind::U8), ("hr", ParamKind::U8), ("sr", ParamKind::U8), ("as", ParamKind::U8),
+    ("qu", ParamKind::U8), ("ap", ParamKind::U16), ("pr", ParamKind::U8), ("gv", ParamKind::U8),
+    ("gh", ParamKind::U8), ("gf", ParamKind::U8), ("gn", ParamKind::U8), ("g1", ParamKind::U8),
+    ("g2", ParamKind::U8), ("g3", ParamKind::U8), ("g4", ParamKind::U8), ("g5", ParamKind::U8),
+];
+
+fn v1_fields() -> impl Iterator<Item = (&'static VoiceParam, ParamKind)> {
+    V1_FIELDS.iter().map(|&(code, kind)| {
+        let param = VoiceParam::find(code).expect("v1 fields are voice parameters");
+        (param, kind)
+    })
+}
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+#[derive(Error, Debug)]
+pub enum VoiceCodeError {
+    #[error("Not a voice code")]
+    InvalidCharacters,
+    #[error("Voice code has the wrong length")]
+    InvalidLength,
+    #[error("Voice code version {0} isn't supported")]
+    UnsupportedVersion(u8),
+    #[error("Voice code is damaged, check that it was copied whole")]
+    ChecksumMismatch,
+    #[error("Voice code holds an invalid voice")]
+    InvalidVoice(Vec<VoiceFieldError>),
+}
+
+impl DECtalkVoice {
+    /// Packs the voice into a short code that can be pasted back with
+    /// [`DECtalkVoice::from_code`].
+    pub fn to_code(&self) -> String {
+        let mut bytes = vec![VERSION];
+        for (param, kind) in v1_fields() {
+            let value = param.get(self);
+            match kind {
+                ParamKind::U8 => bytes.push(value as u8),
+                ParamKind::U16 => bytes.extend(value.to_be_bytes()),
+            }
+        }
+        bytes.extend(fletcher16(&bytes).to_be_bytes());
+
+        encode_base64(&bytes)
+    }
+
+    /// Unpacks a code made by [`DECtalkVoice::to_code`], rejecting codes that
+    /// are damaged or hold values out of range.
+    pub fn from_code(code: &str) -> Result<Self, VoiceCodeError> {
+        let bytes = decode_base64(code.trim()).ok_or(VoiceCodeError::InvalidCharacters)?;
+        let (&version, _) = bytes.split_first().ok_or(VoiceCodeError::InvalidLength)?;
+        if version != VERSION {
+            return Err(VoiceCodeError::UnsupportedVersion(version));
+        }
+
+        let fields_len: usize = V1_FIELDS
+            .iter()
+            .map(|(_, kind)| match kind {
+                ParamKind::U8 => 1,
+                ParamKind::U16 => 2,
+            })
+            .sum();
+        if bytes.len() != 1 + fields_len + 2 {
+            return Err(VoiceCodeError::InvalidLength);
+        }
+
+        let (data, checksum) = bytes.split_at(bytes.len() - 2);
+        if fletcher16(data).to_be_bytes() != checksum {
+            return Err(VoiceCodeError::ChecksumMismatch);
+        }
+
+        let mut voice = DECtalkVoice::default();
+        let mut fields = &data[1..];
+        for (param, kind) in v1_fields() {
+            let value = match kind {
+                ParamKind::U8 => {
+                    let value = fields[0].into();
+                    fields = &fields[1..];
+                    value
+                }
+                ParamKind::U16 => {
+                    let value = u16::from_be_bytes([fields[0], fields[1]]).into();
+                    fields = &fields[2..];
+                    value
+                }
+            };
+            // Always fits, the value was read with the field's size.
+            param.set(&mut voice, value).ok();
+        }
+
+        voice.validate().map_err(VoiceCodeError::InvalidVoice)?;
+        Ok(voice)
+    }
+}
+
+fn fletcher16(data: &[u8]) -> u16 {
+    let (mut a, mut b) = (0u16, 0u16);
+    for &byte in data {
+        a = (a + u16::from(byte)) % 255;
+        b = (b + a) % 255;
+    }
+    (b << 8) | a
+}
+
+// URL-safe base64 without padding, so codes survive being pasted anywhere.
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &byte)| n | u32::from(byte) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+        }
+    }
+    encoded
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    if encoded.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    for chunk in encoded.as_bytes().chunks(4) {
+        let mut n = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            let value = ALPHABET.iter().position(|&a| a == c)? as u32;
+            n |= value << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            bytes.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dectalk::markup::Speaker;
+    use crate::dectalk::{self, VOICE_PARAMS};
+
+    // Paul's code when the layout was introduced, it must keep decoding.
+    const PAUL_CODE: &str = "AQFkDOQOQgEEAUoAAANGAAASEiBkKAB6ZEFGRkpEPDBAVgFQ";
+
+    fn assert_same(a: &DECtalkVoice, b: &DECtalkVoice) {
+        for param in &VOICE_PARAMS {
+            assert_eq!(param.get(a), param.get(b), "{}", param.code);
+        }
+    }
+
+    #[test]
+    fn round_trips_builtin_voices() {
+        for speaker in Speaker::ALL {
+            if let Some(voice) = dectalk::speaker_voice(speaker) {
+                let decoded = DECtalkVoice::from_code(&voice.to_code()).unwrap();
+                assert_same(voice, &decoded);
+            }
+        }
+    }
+
+    #[test]
+    fn v1_layout_is_stable() {
+        let paul = dectalk::default_voice();
+        assert_eq!(paul.to_code(), PAUL_CODE);
+        assert_same(&DECtalkVoice::from_code(PAUL_CODE).unwrap(), paul);
+    }
+
+    #[test]
+    fn rejects_damaged_codes() {
+        let mut code = PAUL_CODE.to_string();
+        code.replace_range(4..5, if &code[4..5] == "A" { "B" } else { "A" });
+        assert!(matches!(
+            DECtalkVoice::from_code(&code),
+            Err(VoiceCodeError::ChecksumMismatch)
+        ));
+        assert!(matches!(
+            DECtalkVoice::from_code("[:np]"),
+            Err(VoiceCodeError::InvalidCharacters)
+        ));
+    }
+}
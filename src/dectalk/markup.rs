@@ -0,0 +1,430 @@
+use std::fmt;
+
+// https://github.com/dectalk/dectalk/blob/develop/src/Txt16bit/apndx_d.txt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Speaker {
+    Paul,
+    Harry,
+    Frank,
+    Dennis,
+    Betty,
+    Ursula,
+    Wendy,
+    Rita,
+    Kit,
+    Val,
+}
+
+impl Speaker {
+    pub const ALL: [Speaker; 10] = [
+        Speaker::Paul,
+        Speaker::Harry,
+        Speaker::Frank,
+        Speaker::Dennis,
+        Speaker::Betty,
+        Speaker::Ursula,
+        Speaker::Wendy,
+        Speaker::Rita,
+        Speaker::Kit,
+        Speaker::Val,
+    ];
+
+    /// Letter used in the `[:nX]` shorthand
+    pub fn code(self) -> char {
+        match self {
+            Speaker::Paul => 'p',
+            Speaker::Harry => 'h',
+            Speaker::Frank => 'f',
+            Speaker::Dennis => 'd',
+            Speaker::Betty => 'b',
+            Speaker::Ursula => 'u',
+            Speaker::Wendy => 'w',
+            Speaker::Rita => 'r',
+            Speaker::Kit => 'k',
+            Speaker::Val => 'v',
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Speaker::Paul => "Paul",
+            Speaker::Harry => "Harry",
+            Speaker::Frank => "Frank",
+            Speaker::Dennis => "Dennis",
+            Speaker::Betty => "Betty",
+            Speaker::Ursula => "Ursula",
+            Speaker::Wendy => "Wendy",
+            Speaker::Rita => "Rita",
+            Speaker::Kit => "Kit",
+            Speaker::Val => "Val",
+        }
+    }
+
+    pub fn from_code(code: char) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|speaker| speaker.code() == code.to_ascii_lowercase())
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|speaker| speaker.name().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// `[:nX]` or `[:name X]`, switch to a built-in speaker
+    Name(Speaker),
+    /// `[:dv ap 90 pr 200]`, change parameters of the current voice
+    DesignVoice(Vec<(String, u32)>),
+    /// `[:rate 200]`, speaking rate in words per minute
+    Rate(u32),
+    /// `[:phoneme on]`, `[:phoneme arpabet speak on]`
+    Phoneme(Vec<String>),
+    /// `[:tone 500 1000]`, frequency in Hz and duration in ms
+    Tone { frequency: u32, duration: u32 },
+    /// `[:dial 555-1234]`, DTMF tones
+    Dial(String),
+    /// `[:comma 100]`, extra pause after commas in ms
+    Comma(u32),
+    /// `[:period 100]`, extra pause after periods in ms
+    Period(u32),
+    /// `[:mode spell on]`
+    Mode { mode: String, on: bool },
+    /// `[:lang us]`
+    Lang(String),
+    /// `[:volume set 50]`
+    Volume(Vec<String>),
+    /// Anything not covered above, kept verbatim
+    Other { name: String, args: Vec<String> },
+}
+
+impl Command {
+    /// Canonical command name, the one used by markup policies
+    pub fn name(&self) -> &str {
+        match self {
+            Command::Name(_) => "name",
+            Command::DesignVoice(_) => "dv",
+            Command::Rate(_) => "rate",
+            Command::Phoneme(_) => "phoneme",
+            Command::Tone { .. } => "tone",
+            Command::Dial(_) => "dial",
+            Command::Comma(_) => "comma",
+            Command::Period(_) => "period",
+            Command::Mode { .. } => "mode",
+            Command::Lang(_) => "lang",
+            Command::Volume(_) => "volume",
+            Command::Other { name, .. } => name,
+        }
+    }
+
+    fn from_parts(name: String, args: Vec<String>) -> Self {
+        let numbers = args
+            .iter()
+            .map(|arg| arg.parse::<u32>().ok())
+            .collect::<Option<Vec<_>>>();
+
+        let command = match (name.as_str(), args.as_slice(), numbers.as_deref()) {
+            ("rate" | "ra", _, Some(&[rate])) => Some(Command::Rate(rate)),
+            ("dv", _, _) => design_voice(&args).map(Command::DesignVoice),
+            ("name", [speaker], _) => Speaker::from_name(speaker).map(Command::Name),
+            (name, [], _) if name.len() == 2 && name.starts_with('n') => name
+                .chars()
+                .nth(1)
+                .and_then(Speaker::from_code)
+                .map(Command::Name),
+            ("phoneme", _, _) => Some(Command::Phoneme(args.clone())),
+            ("tone", _, Some(&[frequency, duration])) => Some(Command::Tone {
+                frequency,
+                duration,
+            }),
+            ("dial", [_, ..], _) => Some(Command::Dial(args.join(" "))),
+            ("comma" | "cp", _, Some(&[duration])) => Some(Command::Comma(duration)),
+            ("period" | "pp", _, Some(&[duration])) => Some(Command::Period(duration)),
+            ("mode", [mode, on], _) if on == "on" || on == "off" => Some(Command::Mode {
+                mode: mode.clone(),
+                on: on == "on",
+            }),
+            ("lang", [lang], _) => Some(Command::Lang(lang.clone())),
+            ("volume" | "vo", _, _) => Some(Command::Volume(args.clone())),
+            _ => None,
+        };
+
+        command.unwrap_or(Command::Other { name, args })
+    }
+}
+
+fn design_voice(args: &[String]) -> Option<Vec<(String, u32)>> {
+    if args.is_empty() || !args.len().is_multiple_of(2) {
+        return None;
+    }
+
+    args.chunks(2)
+        .map(|pair| Some((pair[0].to_ascii_lowercase(), pair[1].parse().ok()?)))
+        .collect()
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Name(speaker) => write!(f, "[:n{}]", speaker.code()),
+            Command::DesignVoice(params) => {
+                write!(f, "[:dv")?;
+                for (param, value) in params {
+                    write!(f, " {} {}", param, value)?;
+                }
+                write!(f, "]")
+            }
+            Command::Rate(rate) => write!(f, "[:rate {}]", rate),
+            Command::Phoneme(args) => write_command(f, "phoneme", args),
+            Command::Tone {
+                frequency,
+                duration,
+            } => write!(f, "[:tone {} {}]", frequency, duration),
+            Command::Dial(digits) => write!(f, "[:dial {}]", digits),
+            Command::Comma(duration) => write!(f, "[:comma {}]", duration),
+            Command::Period(duration) => write!(f, "[:period {}]", duration),
+            Command::Mode { mode, on } => {
+                write!(f, "[:mode {} {}]", mode, if *on { "on" } else { "off" })
+            }
+            Command::Lang(lang) => write!(f, "[:lang {}]", lang),
+            Command::Volume(args) => write_command(f, "volume", args),
+            Command::Other { name, args } => write_command(f, name, args),
+        }
+    }
+}
+
+fn write_command(f: &mut fmt::Formatter<'_>, name: &str, args: &[String]) -> fmt::Result {
+    write!(f, "[:{}", name)?;
+    for arg in args {
+        write!(f, " {}", arg)?;
+    }
+    write!(f, "]")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    /// Plain text, spoken as is
+    Text(String),
+    /// A bare `[...]` bracket, spoken as phonemes when `[:phoneme on]`
+    Phonemes(String),
+    Command(Command),
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Node::Text(text) => write!(f, "{}", text),
+            Node::Phonemes(phonemes) => write!(f, "[{}]", phonemes),
+            Node::Command(command) => write!(f, "{}", command),
+        }
+    }
+}
+
+/// Parses DECtalk markup into nodes.
+///
+/// A bracket holding several commands (`[:np :ra 200]`) becomes one node per
+/// command. An unterminated bracket runs to the end of the input, the same
+/// way the engine reads it.
+pub fn parse(input: &str) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    let mut rest = input;
+
+    while let Some(start) = rest.find('[') {
+        if start > 0 {
+            nodes.push(Node::Text(rest[..start].to_string()));
+        }
+
+        let after = &rest[start + 1..];
+        let (inner, next) = match after.find(']') {
+            Some(end) => (&after[..end], &after[end + 1..]),
+            None => (after, ""),
+        };
+
+        parse_bracket(inner, &mut nodes);
+        rest = next;
+    }
+
+    if !rest.is_empty() {
+        nodes.push(Node::Text(rest.to_string()));
+    }
+
+    nodes
+}
+
+fn parse_bracket(inner: &str, nodes: &mut Vec<Node>) {
+    if !inner.trim_start().starts_with(':') {
+        nodes.push(Node::Phonemes(inner.to_string()));
+        return;
+    }
+
+    let mut current: Option<(String, Vec<String>)> = None;
+    for token in inner.split_whitespace() {
+        if let Some(name) = token.strip_prefix(':') {
+            if let Some((name, args)) = current.take() {
+                nodes.push(Node::Command(Command::from_parts(name, args)));
+            }
+            current = Some((name.to_ascii_lowercase(), Vec::new()));
+        } else if let Some((_, args)) = current.as_mut() {
+            args.push(token.to_string());
+        }
+    }
+
+    if let Some((name, args)) = current {
+        nodes.push(Node::Command(Command::from_parts(name, args)));
+    }
+}
+
+/// Serializes nodes back into a DECtalk string.
+pub fn serialize(nodes: &[Node]) -> String {
+    nodes.iter().map(Node::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(input: &str) -> Command {
+        match parse(input).as_slice() {
+            [Node::Command(command)] => command.clone(),
+            nodes => panic!("expected one command, got {:?}", nodes),
+        }
+    }
+
+    #[test]
+    fn round_trips_every_command() {
+        let inputs = [
+            "[:nh]",
+            "[:dv ap 90 pr 200]",
+            "[:rate 200]",
+            "[:phoneme arpabet speak on]",
+            "[:tone 500 1000]",
+            "[:dial 555-1234]",
+            "[:comma 100]",
+            "[:period 100]",
+            "[:mode spell on]",
+            "[:lang us]",
+            "[:volume set 50]",
+            "[:error speak]",
+        ];
+        for input in inputs {
+            assert_eq!(serialize(&parse(input)), input);
+        }
+    }
+
+    #[test]
+    fn parses_each_variant() {
+        assert_eq!(command("[:nh]"), Command::Name(Speaker::Harry));
+        assert_eq!(
+            command("[:dv AP 90 pr 200]"),
+            Command::DesignVoice(vec![("ap".to_string(), 90), ("pr".to_string(), 200)])
+        );
+        assert_eq!(command("[:ra 200]"), Command::Rate(200));
+        assert_eq!(
+            command("[:phoneme on]"),
+            Command::Phoneme(vec!["on".to_string()])
+        );
+        assert_eq!(
+            command("[:tone 500 1000]"),
+            Command::Tone {
+                frequency: 500,
+                duration: 1000
+            }
+        );
+        assert_eq!(
+            command("[:dial 555 1234]"),
+            Command::Dial("555 1234".to_string())
+        );
+        assert_eq!(command("[:cp 100]"), Command::Comma(100));
+        assert_eq!(command("[:pp 100]"), Command::Period(100));
+        assert_eq!(
+            command("[:mode spell off]"),
+            Command::Mode {
+                mode: "spell".to_string(),
+                on: false
+            }
+        );
+        assert_eq!(command("[:lang us]"), Command::Lang("us".to_string()));
+        assert_eq!(
+            command("[:vo set 50]"),
+            Command::Volume(vec!["set".to_string(), "50".to_string()])
+        );
+    }
+
+    #[test]
+    fn splits_commands_sharing_a_bracket() {
+        assert_eq!(
+            parse("[:np :ra 200]hello"),
+            vec![
+                Node::Command(Command::Name(Speaker::Paul)),
+                Node::Command(Command::Rate(200)),
+                Node::Text("hello".to_string()),
+            ]
+        );
+        assert_eq!(serialize(&parse("[:np :ra 200]")), "[:np][:rate 200]");
+    }
+
+    #[test]
+    fn unterminated_bracket_runs_to_the_end() {
+        assert_eq!(
+            parse("hi [:rate 600 there"),
+            vec![
+                Node::Text("hi ".to_string()),
+                Node::Command(Command::Other {
+                    name: "rate".to_string(),
+                    args: vec!["600".to_string(), "there".to_string()],
+                }),
+            ]
+        );
+        assert_eq!(
+            parse("hi [hx ae"),
+            vec![
+                Node::Text("hi ".to_string()),
+                Node::Phonemes("hx ae".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_speaker_names_and_codes() {
+        assert_eq!(command("[:name paul]"), Command::Name(Speaker::Paul));
+        assert_eq!(command("[:name Betty]"), Command::Name(Speaker::Betty));
+        assert_eq!(command("[:nK]"), Command::Name(Speaker::Kit));
+        for speaker in Speaker::ALL {
+            let input = format!("[:n{}]", speaker.code());
+            assert_eq!(command(&input), Command::Name(speaker));
+        }
+        assert!(matches!(command("[:name nobody]"), Command::Other { .. }));
+        assert!(matches!(command("[:nz]"), Command::Other { .. }));
+    }
+
+    #[test]
+    fn odd_design_voice_arguments_fall_back_to_other() {
+        assert_eq!(
+            command("[:dv ap]"),
+            Command::Other {
+                name: "dv".to_string(),
+                args: vec!["ap".to_string()],
+            }
+        );
+        assert!(matches!(command("[:dv ap high]"), Command::Other { .. }));
+    }
+
+    #[test]
+    fn keeps_text_and_phonemes() {
+        assert_eq!(
+            parse("say [hx ae l ow] now"),
+            vec![
+                Node::Text("say ".to_string()),
+                Node::Phonemes("hx ae l ow".to_string()),
+                Node::Text(" now".to_string()),
+            ]
+        );
+        assert_eq!(
+            serialize(&parse("say [hx ae l ow] now")),
+            "say [hx ae l ow] now"
+        );
+    }
+}
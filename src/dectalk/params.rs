@@ -0,0 +1,111 @@
+use super::{DECtalkVoice, ParseVoiceError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    U8,
+    U16,
+}
+
+/// One `[:dv]` parameter of [`DECtalkVoice`]
+pub struct VoiceParam {
+    /// Short human readable name
+    pub name: &'static str,
+    /// Two letter code used by `[:dv]` and in slash commands
+    pub code: &'static str,
+    pub kind: ParamKind,
+    pub min: u16,
+    pub max: u16,
+    /// Empty for unitless parameters
+    pub unit: &'static str,
+    pub description: &'static str,
+    get: fn(&DECtalkVoice) -> u16,
+    set: fn(&mut DECtalkVoice, u16),
+}
+
+impl VoiceParam {
+    pub fn get(&self, voice: &DECtalkVoice) -> u16 {
+        (self.get)(voice)
+    }
+
+    /// Sets the field, failing only when `value` doesn't fit its type. The
+    /// range is left to [`DECtalkVoice::validate`].
+    pub fn set(&self, voice: &mut DECtalkVoice, value: u32) -> Result<(), ParseVoiceError> {
+        let fits = match self.kind {
+            ParamKind::U8 => value <= u8::MAX.into(),
+            ParamKind::U16 => value <= u16::MAX.into(),
+        };
+        if !fits {
+            return Err(ParseVoiceError::InvalidValue(self.code.to_string(), value));
+        }
+
+        (self.set)(voice, value as u16);
+        Ok(())
+    }
+
+    /// `50-350 Hz`
+    pub fn range(&self) -> String {
+        if self.unit.is_empty() {
+            format!("{}-{}", self.min, self.max)
+        } else {
+            format!("{}-{} {}", self.min, self.max, self.unit)
+        }
+    }
+
+    /// Looks a parameter up by its code, `lo` being the old name of `g5`.
+    pub fn find(code: &str) -> Option<&'static VoiceParam> {
+        let code = match code.to_ascii_lowercase().as_str() {
+            "lo" => "g5".to_string(),
+            code => code.to_string(),
+        };
+        VOICE_PARAMS.iter().find(|param| param.code == code)
+    }
+}
+
+macro_rules! param {
+    ($field:ident, $code:literal, $kind:ident, $min:literal, $max:literal, $unit:literal, $name:literal, $description:literal) => {
+        VoiceParam {
+            name: $name,
+            code: $code,
+            kind: ParamKind::$kind,
+            min: $min,
+            max: $max,
+            unit: $unit,
+            description: $description,
+            get: |voice| voice.$field.into(),
+            set: |voice, value| voice.$field = value as _,
+        }
+    };
+}
+
+// https://github.com/dectalk/dectalk/blob/develop/src/Txt16bit/apndx_d.txt
+#[rustfmt::skip]
+pub static VOICE_PARAMS: [VoiceParam; 28] = [
+    param!(sx,  "sx", U8,  0,    1,    "",   "Sex",               "1 (male) or 0 (female)"),
+    param!(hs,  "hs", U8,  65,   145,  "%",  "Head size",         "Scales every formant, bigger is deeper"),
+    param!(f4,  "f4", U16, 2000, 4650, "Hz", "F4 frequency",      "Fourth formant frequency"),
+    param!(f5,  "f5", U16, 2500, 4950, "Hz", "F5 frequency",      "Fifth formant frequency"),
+    param!(b4,  "b4", U16, 100,  2048, "Hz", "F4 bandwidth",      "Fourth formant bandwidth"),
+    param!(b5,  "b5", U16, 100,  2048, "Hz", "F5 bandwidth",      "Fifth formant bandwidth"),
+    param!(br,  "br", U8,  0,    72,   "dB", "Breathiness",       "Amount of breath noise"),
+    param!(lx,  "lx", U8,  0,    100,  "%",  "Lax breathiness",   "Breathiness of lax vocal folds"),
+    param!(sm,  "sm", U8,  0,    100,  "%",  "Smoothness",        "High frequency attenuation"),
+    param!(ri,  "ri", U8,  0,    100,  "%",  "Richness",          "Brilliance of the voice"),
+    param!(nf,  "nf", U8,  0,    100,  "",   "Fixed samplings",   "Number of fixed samplings of glottal pulse open phase"),
+    param!(la,  "la", U8,  0,    100,  "%",  "Laryngealization",  "Creaky voice"),
+    param!(bf,  "bf", U8,  0,    40,   "Hz", "Baseline fall",     "Pitch drop over a sentence"),
+    param!(hr,  "hr", U8,  2,    100,  "Hz", "Hat rise",          "Pitch rise on stressed phrases"),
+    param!(sr,  "sr", U8,  1,    100,  "Hz", "Stress rise",       "Pitch rise on stressed syllables"),
+    param!(as_, "as", U8,  0,    100,  "%",  "Assertiveness",     "How much the pitch falls at the end"),
+    param!(qu,  "qu", U8,  0,    100,  "%",  "Quickness",         "How fast pitch changes"),
+    param!(ap,  "ap", U16, 50,   350,  "Hz", "Average pitch",     "Mean pitch of the voice"),
+    param!(pr,  "pr", U8,  0,    250,  "%",  "Pitch range",       "Pitch range, 0 is monotone"),
+    param!(gv,  "gv", U8,  0,    86,   "dB", "Voicing gain",      "Gain of voicing source"),
+    param!(gh,  "gh", U8,  0,    86,   "dB", "Aspiration gain",   "Gain of aspiration source"),
+    param!(gf,  "gf", U8,  0,    86,   "dB", "Frication gain",    "Gain of frication source"),
+    param!(gn,  "gn", U8,  0,    86,   "dB", "Nasalization gain", "Gain of nasalization"),
+    param!(g1,  "g1", U8,  0,    86,   "dB", "F1 gain",           "Gain of first formant resonator"),
+    param!(g2,  "g2", U8,  0,    86,   "dB", "F2 gain",           "Gain of second formant resonator"),
+    param!(g3,  "g3", U8,  0,    86,   "dB", "F3 gain",           "Gain of third formant resonator"),
+    param!(g4,  "g4", U8,  0,    86,   "dB", "F4 gain",           "Gain of fourth formant resonator"),
+    param!(g5,  "g5", U8,  0,    86,   "dB", "F5 gain",           "Gain of fifth formant resonator (replaces lo)"),
+];
@@ -0,0 +1,322 @@
+use std::{
+    env,
+    hash::{BuildHasher, RandomState},
+    io,
+    path::{Path, PathBuf},
+    pin::pin,
+    process::Stdio,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use futures::future::{self, Either};
+use tokio::{
+    fs,
+    io::AsyncWriteExt,
+    process::{Child, Command},
+    sync::Semaphore,
+    task, time,
+};
+
+use super::DECtalkError;
+
+const OUTPUT_FILE: &str = "output.wav";
+
+const TEMP_DIR_PREFIX: &str = "dectalk-";
+
+/// How often the output file is checked against `max_output` while `say` runs
+const OUTPUT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone)]
+pub struct DECtalkPoolConfig {
+    /// Path to the `say` binary
+    pub say_path: PathBuf,
+    /// Number of idle `say` processes kept warm, waiting on stdin
+    pub workers: usize,
+    /// Maximum number of utterances synthesized at the same time
+    pub max_concurrency: usize,
+    /// Wall-clock limit for a single utterance, the process is killed after it
+    pub timeout: Duration,
+    /// Largest WAV file, in bytes, a single utterance may write, the process
+    /// is killed once its output grows past it
+    pub max_output: u64,
+}
+
+impl Default for DECtalkPoolConfig {
+    fn default() -> Self {
+        Self {
+            say_path: PathBuf::from("dectalk/say"),
+            // One warm worker per concurrent utterance, so none start cold
+            workers: 4,
+            max_concurrency: 4,
+            timeout: Duration::from_secs(30),
+            // A bit over 5 minutes of 11025 Hz 16-bit mono
+            max_output: 8 * 1024 * 1024,
+        }
+    }
+}
+
+// A private directory under the system temp dir, removed with everything in
+// it when dropped so a failed or cancelled synthesis leaves nothing behind.
+struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    // The name is `dectalk-<pid>-<random>`. It can't be guessed ahead of time
+    // and doesn't clash with directories left behind by an earlier run that
+    // had the same PID, as happens when running as PID 1 in a container.
+    fn new(id: u64) -> io::Result<Self> {
+        let mut builder = std::fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+        let mut attempt = 0;
+        loop {
+            // `RandomState` keys are seeded by the OS once per thread and
+            // bumped for each new one, so names can't be guessed outside.
+            let random = RandomState::new().hash_one((id, attempt));
+            let path = env::temp_dir().join(format!(
+                "{}{}-{:016x}",
+                TEMP_DIR_PREFIX,
+                std::process::id(),
+                random
+            ));
+
+            match builder.create(&path) {
+                Ok(()) => return Ok(Self { path }),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists && attempt < 8 => {
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_dir_all(&self.path) {
+            log::warn!("Failed to remove {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+// Removes directories left behind by earlier runs that didn't get to clean
+// up, those of processes that are gone and those with our own PID, which
+// can't be ours yet. Must only run before the pool starts its workers.
+fn remove_stale_dirs() {
+    let Ok(entries) = std::fs::read_dir(env::temp_dir()) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(pid) = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(TEMP_DIR_PREFIX))
+            .and_then(|rest| rest.split_once('-'))
+            .and_then(|(pid, _)| pid.parse::<u32>().ok())
+        else {
+            continue;
+        };
+
+        if pid != std::process::id() && !process_exited(pid) {
+            continue;
+        }
+
+        if let Err(err) = std::fs::remove_dir_all(entry.path()) {
+            log::warn!("Failed to remove {}: {}", entry.path().display(), err);
+        }
+    }
+}
+
+// Only Linux is checked through `/proc`, elsewhere every other process is
+// assumed to still be running.
+fn process_exited(pid: u32) -> bool {
+    cfg!(target_os = "linux") && !Path::new("/proc").join(pid.to_string()).exists()
+}
+
+// A `say` process that has already started up and loaded its dictionary,
+// blocked reading its input from stdin. The child is declared first so it is
+// killed before its output directory is removed.
+struct DECtalkProcess {
+    child: Child,
+    dir: TempDir,
+}
+
+impl DECtalkProcess {
+    fn output(&self) -> PathBuf {
+        self.dir.path().join(OUTPUT_FILE)
+    }
+}
+
+// Idle workers and how many more are being started, behind one lock so
+// concurrent refills don't start more than `workers` between them.
+#[derive(Default)]
+struct Idle {
+    processes: Vec<DECtalkProcess>,
+    starting: usize,
+}
+
+struct Workers {
+    config: DECtalkPoolConfig,
+    idle: Mutex<Idle>,
+    next_id: AtomicU64,
+}
+
+impl Workers {
+    fn spawn(&self) -> Result<DECtalkProcess, DECtalkError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let dir = TempDir::new(id)?;
+        let output = dir.path().join(OUTPUT_FILE);
+
+        let child = Command::new(&self.config.say_path)
+            .arg("-fo")
+            .arg(&output)
+            .arg("-fi")
+            .arg("/dev/stdin")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        Ok(DECtalkProcess { child, dir })
+    }
+
+    // Starts workers until `workers` are idle or starting. Blocks on the file
+    // system and on process creation, so it runs off the async threads.
+    fn refill(&self) {
+        loop {
+            {
+                let mut idle = self.idle.lock().expect("pool lock poisoned");
+                if idle.processes.len() + idle.starting >= self.config.workers {
+                    return;
+                }
+                idle.starting += 1;
+            }
+
+            let process = self.spawn();
+            let mut idle = self.idle.lock().expect("pool lock poisoned");
+            idle.starting -= 1;
+            match process {
+                Ok(process) => idle.processes.push(process),
+                Err(err) => {
+                    log::warn!("Failed to start DECtalk worker: {}", err);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<DECtalkProcess> {
+        let mut idle = self.idle.lock().expect("pool lock poisoned");
+        while let Some(mut process) = idle.processes.pop() {
+            // Skip workers that exited while they were waiting for input.
+            if let Ok(None) = process.child.try_wait() {
+                return Some(process);
+            }
+        }
+        None
+    }
+}
+
+pub struct DECtalkPool {
+    workers: Arc<Workers>,
+    permits: Semaphore,
+}
+
+impl DECtalkPool {
+    pub fn new(config: DECtalkPoolConfig) -> Self {
+        let pool = Self {
+            permits: Semaphore::new(config.max_concurrency.max(1)),
+            workers: Arc::new(Workers {
+                idle: Mutex::new(Idle::default()),
+                next_id: AtomicU64::new(0),
+                config,
+            }),
+        };
+        remove_stale_dirs();
+        pool.workers.refill();
+        pool
+    }
+
+    async fn take(&self) -> Result<DECtalkProcess, DECtalkError> {
+        let process = self.workers.pop();
+
+        // Start the replacement right away, so it has loaded by the time the
+        // next utterance needs it.
+        let workers = self.workers.clone();
+        task::spawn_blocking(move || workers.refill());
+
+        match process {
+            Some(process) => Ok(process),
+            None => {
+                let workers = self.workers.clone();
+                task::spawn_blocking(move || workers.spawn())
+                    .await
+                    .expect("worker spawn panicked")
+            }
+        }
+    }
+
+    pub async fn synthesize(&self, input: &str) -> Result<Vec<u8>, DECtalkError> {
+        let _permit = self.permits.acquire().await.expect("pool semaphore closed");
+
+        let process = self.take().await?;
+        // Dropping `run` on timeout (or when the caller is cancelled) drops
+        // the child, which kills it.
+        time::timeout(self.workers.config.timeout, self.run(process, input))
+            .await
+            .unwrap_or(Err(DECtalkError::Timeout))
+    }
+
+    async fn run(&self, mut process: DECtalkProcess, input: &str) -> Result<Vec<u8>, DECtalkError> {
+        let mut stdin = process.child.stdin.take().expect("stdin is piped");
+        stdin.write_all(input.as_bytes()).await?;
+        stdin.write_all(b"\n").await?;
+        // Closing stdin is what tells `say` the input is complete.
+        drop(stdin);
+
+        let path = process.output();
+        // Give up as soon as the output grows past the limit instead of
+        // letting `say` fill the disk, dropping the wait kills the child.
+        let watch = async {
+            loop {
+                time::sleep(OUTPUT_POLL_INTERVAL).await;
+                if let Ok(metadata) = fs::metadata(&path).await
+                    && metadata.len() > self.workers.config.max_output
+                {
+                    return DECtalkError::OutputTooLarge(metadata.len());
+                }
+            }
+        };
+        let wait = process.child.wait_with_output();
+        let output = match future::select(pin!(wait), pin!(watch)).await {
+            Either::Left((output, _)) => output?,
+            Either::Right((err, _)) => return Err(err),
+        };
+        if !output.status.success() {
+            return Err(DECtalkError::SayError(
+                String::from_utf8_lossy(&output.stderr).to_string(),
+            ));
+        }
+
+        let len = fs::metadata(&path).await?.len();
+        if len > self.workers.config.max_output {
+            return Err(DECtalkError::OutputTooLarge(len));
+        }
+
+        // The temp dir goes away with `process.dir` once this returns.
+        let bytes = fs::read(&path).await?;
+        Ok(bytes)
+    }
+}
@@ -0,0 +1,90 @@
+use super::markup::{self, Node};
+
+/// Removes DECtalk markup from `text` unless `allowed` accepts it.
+///
+/// Commands are checked by their canonical name ([`markup::Command::name`]),
+/// so `[:ra 300]` and `[:rate 300]` are both `rate` and `[:np]` is `name`.
+/// Bare phoneme brackets (`[hx'ae<300,20>]`) are checked as `phoneme`, and
+/// are always removed when they hold a `[` or `:`, since the engine may read
+/// those as the start of a command (`[[:rate 600]`, `[x :dv ap 350]`).
+pub fn sanitize(text: &str, allowed: impl Fn(&str) -> bool) -> String {
+    markup::parse(text)
+        .into_iter()
+        .map(|node| match &node {
+            Node::Text(_) => node.to_string(),
+            Node::Phonemes(phonemes) if !phonemes.contains(['[', ':']) && allowed("phoneme") => {
+                node.to_string()
+            }
+            Node::Command(command) if allowed(command.name()) => node.to_string(),
+            _ => " ".to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markup_manager::{MarkupMode, MarkupPolicy};
+
+    fn sanitize_default(text: &str) -> String {
+        let policy = MarkupPolicy::default();
+        sanitize(text, |command| policy.allows(command))
+    }
+
+    #[test]
+    fn keeps_plain_text() {
+        assert_eq!(sanitize_default("hello world"), "hello world");
+    }
+
+    #[test]
+    fn default_keeps_phonemes() {
+        assert_eq!(
+            sanitize_default("say [hx'ae<300,20>] now"),
+            "say [hx'ae<300,20>] now"
+        );
+    }
+
+    #[test]
+    fn default_strips_commands() {
+        assert_eq!(sanitize_default("a [:rate 600] b"), "a   b");
+        assert_eq!(sanitize_default("a [:ra 600] b"), "a   b");
+        assert_eq!(sanitize_default("[:np :dv ap 350]hi"), "  hi");
+        assert_eq!(sanitize_default("[:tone 500 10000"), " ");
+    }
+
+    #[test]
+    fn strips_commands_hidden_in_phonemes() {
+        assert_eq!(sanitize_default("[[:rate 600]"), " ");
+        assert_eq!(sanitize_default("a [x :dv ap 350] b"), "a   b");
+    }
+
+    #[test]
+    fn strips_nested_phonemes_even_when_allowed() {
+        let allow_all = MarkupPolicy {
+            mode: MarkupMode::All,
+            whitelist: Default::default(),
+        };
+        let sanitized = sanitize("[[:rate 600] [hx'ae]", |command| allow_all.allows(command));
+        assert_eq!(sanitized, "  [hx'ae]");
+    }
+
+    #[test]
+    fn strip_mode_removes_everything() {
+        let strip = MarkupPolicy {
+            mode: MarkupMode::Strip,
+            whitelist: Default::default(),
+        };
+        let sanitized = sanitize("a [hx'ae] [:np] b", |command| strip.allows(command));
+        assert_eq!(sanitized, "a     b");
+    }
+
+    #[test]
+    fn whitelist_allows_listed_commands() {
+        let policy = MarkupPolicy {
+            mode: MarkupMode::Whitelist,
+            whitelist: ["rate".to_string()].into(),
+        };
+        let sanitized = sanitize("[:ra 300] [:np]", |command| policy.allows(command));
+        assert_eq!(sanitized, "[:rate 300]  ");
+    }
+}
@@ -0,0 +1,96 @@
+use std::{collections::HashMap, io};
+
+use crate::dectalk::Language;
+use thiserror::Error;
+use tokio::fs;
+
+#[derive(Error, Debug)]
+pub enum LanguageManagerError {
+    #[error("IO error: {0}")]
+    IOError(#[from] io::Error),
+    #[error("Serde error: {0}")]
+    SerdeError(#[from] serde_json::Error),
+}
+
+pub struct LanguageManager {
+    pub installed: Vec<Language>,
+    pub users: HashMap<u64, Language>,
+    pub guilds: HashMap<u64, Language>,
+}
+
+impl LanguageManager {
+    pub fn new(installed: Vec<Language>) -> Self {
+        Self {
+            installed,
+            users: HashMap::new(),
+            guilds: HashMap::new(),
+        }
+    }
+
+    /// The user's language, else the guild's, else US English. Languages
+    /// that are no longer installed are skipped.
+    pub fn get(&self, guild_id: Option<u64>, user_id: u64) -> Language {
+        let guild = guild_id.and_then(|guild_id| self.guilds.get(&guild_id));
+
+        [self.users.get(&user_id), guild]
+            .into_iter()
+            .flatten()
+            .copied()
+            .find(|language| self.installed.contains(language))
+            .unwrap_or_default()
+    }
+
+    pub fn get_user(&self, user_id: u64) -> Option<Language> {
+        self.users.get(&user_id).copied()
+    }
+
+    pub fn get_guild(&self, guild_id: u64) -> Option<Language> {
+        self.guilds.get(&guild_id).copied()
+    }
+
+    pub async fn set_user(
+        &mut self,
+        user_id: u64,
+        language: Option<Language>,
+    ) -> Result<(), LanguageManagerError> {
+        match language {
+            Some(language) => self.users.insert(user_id, language),
+            None => self.users.remove(&user_id),
+        };
+        self.save().await?;
+        Ok(())
+    }
+
+    pub async fn set_guild(
+        &mut self,
+        guild_id: u64,
+        language: Option<Language>,
+    ) -> Result<(), LanguageManagerError> {
+        match language {
+            Some(language) => self.guilds.insert(guild_id, language),
+            None => self.guilds.remove(&guild_id),
+        };
+        self.save().await?;
+        Ok(())
+    }
+
+    pub async fn can_load(&self) -> bool {
+        fs::metadata("data/languages.json").await.is_ok()
+    }
+
+    pub async fn load(&mut self) -> Result<(), LanguageManagerError> {
+        let users_string = fs::read_to_string("data/languages.json").await?;
+        self.users = serde_json::from_str(&users_string)?;
+        let guilds_string = fs::read_to_string("data/guild_languages.json").await?;
+        self.guilds = serde_json::from_str(&guilds_string)?;
+        Ok(())
+    }
+
+    pub async fn save(&self) -> Result<(), LanguageManagerError> {
+        let users_string = serde_json::to_string(&self.users)?;
+        fs::write("data/languages.json", users_string).await?;
+        let guilds_string = serde_json::to_string(&self.guilds)?;
+        fs::write("data/guild_languages.json", guilds_string).await?;
+        Ok(())
+    }
+}
@@ -1,125 +1,899 @@
-use std::{collections::HashSet, env, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
 
 use dectalk::tts;
 use dotenv::dotenv;
-use futures::{Stream, StreamExt, future, stream};
+use futures::{StreamExt, stream};
+use language_manager::LanguageManager;
+use markup_manager::{MarkupManager, MarkupMode, MarkupPolicy};
 use mute_manager::MuteManager;
 use poise::serenity_prelude as serenity;
 use prefix_manager::PrefixManager;
-use songbird::{SerenityInit, input::Input};
+use preset_manager::PresetManager;
+use songbird::{
+    SerenityInit,
+    input::Input,
+    tracks::{Track, TrackHandle, TrackQueue},
+};
 use tokio::sync::Mutex;
 use voice_manager::VoiceManager;
 
 mod dectalk;
+mod language_manager;
+mod markup_manager;
 mod mute_manager;
 mod prefix_manager;
+mod preset_manager;
+mod transliterate;
 mod utils;
+mod voice_editor;
 mod voice_manager;
 
 struct Data {
     voice_manager: Arc<Mutex<VoiceManager>>,
     mute_manager: Arc<Mutex<MuteManager>>,
     prefix_manager: Arc<Mutex<PrefixManager>>,
+    markup_manager: Arc<Mutex<MarkupManager>>,
+    language_manager: Arc<Mutex<LanguageManager>>,
+    preset_manager: PresetManager,
+    // Held while a message is synthesized and queued, so messages in a guild
+    // are queued in order without keeping its call locked meanwhile.
+    speech_locks: Mutex<HashMap<serenity::GuildId, Arc<Mutex<()>>>>,
     tts_peak: f32,
     tts_len: usize,
+    tts_replacement: Option<String>,
 }
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, Data, Error>;
 
-#[poise::command(slash_command, ephemeral)]
-async fn voice(
+/// Number of sentences synthesized ahead of the one being queued
+const TTS_LOOKAHEAD: usize = 2;
+
+/// Attached to every track queued for a message, shared by its sentences
+struct SpokenMessage {
+    id: serenity::MessageId,
+    // Set when the message is skipped, so sentences still being synthesized
+    // aren't queued after it.
+    skipped: AtomicBool,
+}
+
+impl SpokenMessage {
+    fn of(track: &TrackHandle) -> Arc<Self> {
+        track.data()
+    }
+}
+
+// Takes the tracks waiting behind the current one out of the queue, those
+// for which `remove` is true given the current message and theirs.
+fn remove_queued(
+    queue: &TrackQueue,
+    remove: impl Fn(serenity::MessageId, serenity::MessageId) -> bool,
+) -> Vec<TrackHandle> {
+    queue.modify_queue(|queue| {
+        let Some(current) = queue.front().map(|track| SpokenMessage::of(track).id) else {
+            return Vec::new();
+        };
+        let mut removed = Vec::new();
+        let mut index = 1;
+        while index < queue.len() {
+            if remove(current, SpokenMessage::of(&queue[index]).id) {
+                removed.extend(queue.remove(index).map(|track| track.handle()));
+            } else {
+                index += 1;
+            }
+        }
+        removed
+    })
+}
+
+// Stops the tracks, and the sentences of their messages that are still being
+// synthesized from being queued after them.
+fn stop_messages(tracks: &[TrackHandle]) {
+    for track in tracks {
+        SpokenMessage::of(track)
+            .skipped
+            .store(true, Ordering::Relaxed);
+        let _ = track.stop();
+    }
+}
+
+fn invalid_voice_message(errors: &[dectalk::VoiceFieldError]) -> String {
+    let mut message = "Invalid voice!".to_string();
+    for error in errors {
+        message.push_str(&format!("\n- {}", error));
+    }
+    message
+}
+
+#[poise::command(
+    slash_command,
+    subcommands(
+        "voice_source",
+        "voice_formants",
+        "voice_prosody",
+        "voice_gains",
+        "voice_param",
+        "voice_edit",
+        "voice_save",
+        "voice_load",
+        "voice_list",
+        "voice_rename",
+        "voice_delete",
+        "voice_guildset",
+        "voice_guildclear",
+        "voice_blend",
+        "voice_random",
+        "voice_share",
+        "voice_use",
+        "voice_analyze",
+        "voice_undo",
+        "voice_redo",
+        "voice_history",
+        "voice_import",
+        "voice_export"
+    ),
+    subcommand_required
+)]
+async fn voice(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Applies the given `[:dv]` code/value pairs to the author's voice, then
+/// validates and saves it.
+async fn update_voice(ctx: Context<'_>, options: &[(&str, Option<u16>)]) -> Result<(), Error> {
+    let author = ctx.author();
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+    let mut voice = voice_manager.get_user(author.id.get()).clone();
+
+    for &(code, value) in options {
+        if let Some(value) = value {
+            voice.set(code, value.into())?;
+        }
+    }
+
+    if let Err(errors) = voice.validate() {
+        ctx.say(invalid_voice_message(&errors)).await?;
+        return Ok(());
+    }
+
+    voice_manager.set(author.id.get(), &voice).await?;
+    ctx.say(format!("```rust\n{:?}\n```", voice)).await?;
+    Ok(())
+}
+
+// Discord allows 25 options per command, so the parameters are split over
+// several subcommands. Option descriptions are filled in from the parameter
+// registry, see `describe_voice_params`.
+
+/// Change the voice source: sex, breathiness and glottal pulse shape
+#[poise::command(slash_command, ephemeral, rename = "source")]
+async fn voice_source(
+    ctx: Context<'_>,
+    sx: Option<u8>,
+    br: Option<u8>,
+    lx: Option<u8>,
+    sm: Option<u8>,
+    ri: Option<u8>,
+    nf: Option<u8>,
+    la: Option<u8>,
+) -> Result<(), Error> {
+    update_voice(
+        ctx,
+        &[
+            ("sx", sx.map(Into::into)),
+            ("br", br.map(Into::into)),
+            ("lx", lx.map(Into::into)),
+            ("sm", sm.map(Into::into)),
+            ("ri", ri.map(Into::into)),
+            ("nf", nf.map(Into::into)),
+            ("la", la.map(Into::into)),
+        ],
+    )
+    .await
+}
+
+/// Change the head size and the higher formants
+#[poise::command(slash_command, ephemeral, rename = "formants")]
+async fn voice_formants(
+    ctx: Context<'_>,
+    hs: Option<u8>,
+    f4: Option<u16>,
+    f5: Option<u16>,
+    b4: Option<u16>,
+    b5: Option<u16>,
+) -> Result<(), Error> {
+    update_voice(
+        ctx,
+        &[
+            ("hs", hs.map(Into::into)),
+            ("f4", f4),
+            ("f5", f5),
+            ("b4", b4),
+            ("b5", b5),
+        ],
+    )
+    .await
+}
+
+/// Change the pitch and intonation
+#[poise::command(slash_command, ephemeral, rename = "prosody")]
+async fn voice_prosody(
+    ctx: Context<'_>,
+    ap: Option<u16>,
+    pr: Option<u8>,
+    bf: Option<u8>,
+    hr: Option<u8>,
+    sr: Option<u8>,
+    #[rename = "as"] as_: Option<u8>,
+    qu: Option<u8>,
+) -> Result<(), Error> {
+    update_voice(
+        ctx,
+        &[
+            ("ap", ap),
+            ("pr", pr.map(Into::into)),
+            ("bf", bf.map(Into::into)),
+            ("hr", hr.map(Into::into)),
+            ("sr", sr.map(Into::into)),
+            ("as", as_.map(Into::into)),
+            ("qu", qu.map(Into::into)),
+        ],
+    )
+    .await
+}
+
+/// Change the source and formant resonator gains
+#[poise::command(slash_command, ephemeral, rename = "gains")]
+async fn voice_gains(
+    ctx: Context<'_>,
+    gv: Option<u8>,
+    gh: Option<u8>,
+    gf: Option<u8>,
+    gn: Option<u8>,
+    g1: Option<u8>,
+    g2: Option<u8>,
+    g3: Option<u8>,
+    g4: Option<u8>,
+    g5: Option<u8>,
+) -> Result<(), Error> {
+    update_voice(
+        ctx,
+        &[
+            ("gv", gv.map(Into::into)),
+            ("gh", gh.map(Into::into)),
+            ("gf", gf.map(Into::into)),
+            ("gn", gn.map(Into::into)),
+            ("g1", g1.map(Into::into)),
+            ("g2", g2.map(Into::into)),
+            ("g3", g3.map(Into::into)),
+            ("g4", g4.map(Into::into)),
+            ("g5", g5.map(Into::into)),
+        ],
+    )
+    .await
+}
+
+async fn autocomplete_voice_param(
+    _ctx: Context<'_>,
+    partial: &str,
+) -> impl Iterator<Item = serenity::AutocompleteChoice> {
+    let partial = partial.to_lowercase();
+
+    dectalk::VOICE_PARAMS
+        .iter()
+        .filter(move |param| {
+            param.code.starts_with(&partial) || param.name.to_lowercase().contains(&partial)
+        })
+        .take(25)
+        .map(|param| {
+            serenity::AutocompleteChoice::new(
+                format!("{}: {} ({})", param.code, param.name, param.range()),
+                param.code,
+            )
+        })
+}
+
+#[poise::command(slash_command, ephemeral, rename = "param")]
+async fn voice_param(
+    ctx: Context<'_>,
+    #[description = "Parameter to show or change"]
+    #[autocomplete = "autocomplete_voice_param"]
+    parameter: String,
+    #[description = "New value"] value: Option<u16>,
+) -> Result<(), Error> {
+    let author = ctx.author();
+
+    let Some(param) = dectalk::VoiceParam::find(&parameter) else {
+        ctx.say("Invalid parameter!").await?;
+        return Ok(());
+    };
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+    let mut voice = voice_manager.get_user(author.id.get()).clone();
+
+    if let Some(value) = value {
+        param.set(&mut voice, value.into())?;
+        if let Err(errors) = voice.validate() {
+            ctx.say(invalid_voice_message(&errors)).await?;
+            return Ok(());
+        }
+        voice_manager.set(author.id.get(), &voice).await?;
+    }
+
+    ctx.say(format!(
+        "`{}` {}: `{}` ({})\n{}",
+        param.code,
+        param.name,
+        param.get(&voice),
+        param.range(),
+        param.description
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Describes every option named after a voice parameter, so the slash
+/// command help comes from the same registry as validation.
+fn describe_voice_params(command: &mut poise::Command<Data, Error>) {
+    for parameter in &mut command.parameters {
+        if let Some(param) = dectalk::VoiceParam::find(&parameter.name) {
+            parameter.description = Some(format!(
+                "{} ({}): {}",
+                param.name,
+                param.range(),
+                param.description
+            ));
+        }
+    }
+
+    for subcommand in &mut command.subcommands {
+        describe_voice_params(subcommand);
+    }
+}
+
+#[poise::command(slash_command, ephemeral, rename = "edit")]
+async fn voice_edit(ctx: Context<'_>) -> Result<(), Error> {
+    voice_editor::run(ctx).await
+}
+
+async fn autocomplete_saved_voice(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<serenity::AutocompleteChoice> {
+    let partial = partial.to_lowercase();
+    let voice_manager = ctx.data().voice_manager.lock().await;
+
+    voice_manager
+        .saved_names(ctx.author().id.get())
+        .into_iter()
+        .filter(|name| name.to_lowercase().contains(&partial))
+        .take(25)
+        .map(|name| serenity::AutocompleteChoice::new(name, name))
+        .collect()
+}
+
+#[poise::command(slash_command, ephemeral, rename = "save")]
+async fn voice_save(
+    ctx: Context<'_>,
+    #[description = "Name to save your current voice as"]
+    #[autocomplete = "autocomplete_saved_voice"]
+    #[max_length = 32]
+    name: String,
+) -> Result<(), Error> {
+    let author = ctx.author();
+    let name = name.trim();
+
+    if name.is_empty() {
+        ctx.say("Invalid voice name!").await?;
+        return Ok(());
+    }
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+    let voice = voice_manager.get_user(author.id.get()).clone();
+    voice_manager
+        .save_voice(author.id.get(), name, &voice)
+        .await?;
+
+    ctx.say(format!("Saved your voice as \"{}\"", name)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, rename = "load")]
+async fn voice_load(
+    ctx: Context<'_>,
+    #[description = "Saved voice to switch to"]
+    #[autocomplete = "autocomplete_saved_voice"]
+    name: String,
+) -> Result<(), Error> {
+    let author = ctx.author();
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+    let Some(voice) = voice_manager.get_saved(author.id.get(), &name).cloned() else {
+        ctx.say(format!("You have no voice named \"{}\"", name))
+            .await?;
+        return Ok(());
+    };
+
+    voice_manager.set(author.id.get(), &voice).await?;
+    ctx.say(format!("```rust\n{:?}\n```", voice)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, rename = "list")]
+async fn voice_list(ctx: Context<'_>) -> Result<(), Error> {
+    let author = ctx.author();
+
+    let voice_manager = ctx.data().voice_manager.lock().await;
+    let names = voice_manager.saved_names(author.id.get());
+
+    if names.is_empty() {
+        ctx.say("You have no saved voices, use `/voice save` to add one.")
+            .await?;
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "Saved voices ({}/{}):",
+        names.len(),
+        voice_manager::MAX_SAVED_VOICES
+    );
+    for name in names {
+        message.push_str(&format!("\n- {}", name));
+    }
+    ctx.say(message).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, rename = "rename")]
+async fn voice_rename(
+    ctx: Context<'_>,
+    #[description = "Saved voice to rename"]
+    #[autocomplete = "autocomplete_saved_voice"]
+    name: String,
+    #[description = "New name"]
+    #[max_length = 32]
+    new_name: String,
+) -> Result<(), Error> {
+    let author = ctx.author();
+    let new_name = new_name.trim();
+
+    if new_name.is_empty() {
+        ctx.say("Invalid voice name!").await?;
+        return Ok(());
+    }
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+    voice_manager
+        .rename_voice(author.id.get(), &name, new_name)
+        .await?;
+
+    ctx.say(format!("Renamed \"{}\" to \"{}\"", name, new_name))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, rename = "delete")]
+async fn voice_delete(
+    ctx: Context<'_>,
+    #[description = "Saved voice to delete"]
+    #[autocomplete = "autocomplete_saved_voice"]
+    name: String,
+) -> Result<(), Error> {
+    let author = ctx.author();
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+    voice_manager.delete_voice(author.id.get(), &name).await?;
+
+    ctx.say(format!("Deleted \"{}\"", name)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, rename = "guildset")]
+async fn voice_guildset(
+    ctx: Context<'_>,
+    #[description = "Saved voice to use in this server, defaults to your current voice"]
+    #[autocomplete = "autocomplete_saved_voice"]
+    name: Option<String>,
+) -> Result<(), Error> {
+    let author = ctx.author();
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+    let voice = match name {
+        Some(name) => match voice_manager.get_saved(author.id.get(), &name) {
+            Some(voice) => voice.clone(),
+            None => {
+                ctx.say(format!("You have no voice named \"{}\"", name))
+                    .await?;
+                return Ok(());
+            }
+        },
+        None => voice_manager.get_user(author.id.get()).clone(),
+    };
+
+    voice_manager
+        .set_guild_voice(guild_id.get(), author.id.get(), Some(&voice))
+        .await?;
+
+    ctx.say(format!(
+        "This voice is now used in this server instead of your global one. \
+         `/voice` commands still change your global voice.\n```rust\n{:?}\n```",
+        voice
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, guild_only, rename = "guildclear")]
+async fn voice_guildclear(ctx: Context<'_>) -> Result<(), Error> {
+    let author = ctx.author();
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+    voice_manager
+        .set_guild_voice(guild_id.get(), author.id.get(), None)
+        .await?;
+
+    let voice = voice_manager.get(Some(guild_id.get()), author.id.get());
+    ctx.say(format!("```rust\n{:?}\n```", voice)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, rename = "blend")]
+async fn voice_blend(
+    ctx: Context<'_>,
+    #[description = "Preset to start from"]
+    #[autocomplete = "autocomplete_voice_preset"]
+    preset_a: String,
+    #[description = "Preset to move towards"]
+    #[autocomplete = "autocomplete_voice_preset"]
+    preset_b: String,
+    #[description = "How far to move towards preset_b, in percent"]
+    #[min = 0]
+    #[max = 100]
+    amount: u8,
+) -> Result<(), Error> {
+    let author = ctx.author();
+    let preset_manager = &ctx.data().preset_manager;
+
+    let (Some(a), Some(b)) = (preset_manager.get(&preset_a), preset_manager.get(&preset_b)) else {
+        ctx.say("Invalid voice preset!").await?;
+        return Ok(());
+    };
+
+    let voice = dectalk::DECtalkVoice::lerp(&a.voice, &b.voice, f32::from(amount) / 100.0);
+    if let Err(errors) = voice.validate() {
+        ctx.say(invalid_voice_message(&errors)).await?;
+        return Ok(());
+    }
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+    voice_manager.set(author.id.get(), &voice).await?;
+    ctx.say(format!("```rust\n{:?}\n```", voice)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, rename = "random")]
+async fn voice_random(
+    ctx: Context<'_>,
+    #[description = "Natural (default) or wild"] mode: Option<voice_editor::RandomMode>,
+    #[description = "Parameters to keep, e.g. \"sx hs\""] keep: Option<String>,
+    #[description = "Ranges to stay in, e.g. \"ap 80-120, pr 100-200\""] lock: Option<String>,
+) -> Result<(), Error> {
+    let voice = {
+        let voice_manager = ctx.data().voice_manager.lock().await;
+        voice_manager.get_user(ctx.author().id.get()).clone()
+    };
+
+    let limits = match voice_editor::parse_limits(&voice, keep.as_deref(), lock.as_deref()) {
+        Ok(limits) => limits,
+        Err(err) => {
+            ctx.say(err).await?;
+            return Ok(());
+        }
+    };
+
+    voice_editor::random(
+        ctx,
+        mode.unwrap_or(voice_editor::RandomMode::Natural),
+        limits,
+    )
+    .await
+}
+
+#[poise::command(slash_command, ephemeral, rename = "share")]
+async fn voice_share(ctx: Context<'_>) -> Result<(), Error> {
+    let author = ctx.author();
+
+    let voice_manager = ctx.data().voice_manager.lock().await;
+    let voice = voice_manager.get_user(author.id.get());
+
+    ctx.say(format!(
+        "Anyone can use this voice with `/voice use`:\n```\n{}\n```",
+        voice.to_code()
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, rename = "use")]
+async fn voice_use(
+    ctx: Context<'_>,
+    #[description = "Code from /voice share"] code: String,
+) -> Result<(), Error> {
+    let author = ctx.author();
+
+    let voice = match dectalk::DECtalkVoice::from_code(&code) {
+        Ok(voice) => voice,
+        Err(dectalk::VoiceCodeError::InvalidVoice(errors)) => {
+            ctx.say(invalid_voice_message(&errors)).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            ctx.say(format!("Invalid voice code: {}", err)).await?;
+            return Ok(());
+        }
+    };
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+    voice_manager.set(author.id.get(), &voice).await?;
+    ctx.say(format!("```rust\n{:?}\n```", voice)).await?;
+    Ok(())
+}
+
+/// Number of presets and of differing parameters `/voice analyze` lists
+const ANALYZE_LEN: usize = 3;
+
+#[poise::command(slash_command, ephemeral, rename = "analyze")]
+async fn voice_analyze(
+    ctx: Context<'_>,
+    #[description = "Voice code or string to analyze, defaults to your current voice"]
+    voice: Option<String>,
+) -> Result<(), Error> {
+    let author = ctx.author();
+
+    let voice = match voice {
+        Some(voice) => match dectalk::DECtalkVoice::from_code(&voice) {
+            Ok(voice) => voice,
+            // Only something that isn't a code at all is read as a voice string.
+            Err(dectalk::VoiceCodeError::InvalidCharacters) => {
+                match voice.parse::<dectalk::DECtalkVoice>() {
+                    Ok(voice) => voice,
+                    Err(err) => {
+                        ctx.say(format!("Invalid voice string: {}", err)).await?;
+                        return Ok(());
+                    }
+                }
+            }
+            Err(dectalk::VoiceCodeError::InvalidVoice(errors)) => {
+                ctx.say(invalid_voice_message(&errors)).await?;
+                return Ok(());
+            }
+            Err(err) => {
+                ctx.say(format!("Invalid voice code: {}", err)).await?;
+                return Ok(());
+            }
+        },
+        None => {
+            let voice_manager = ctx.data().voice_manager.lock().await;
+            voice_manager.get_user(author.id.get()).clone()
+        }
+    };
+
+    let mut presets: Vec<_> = ctx
+        .data()
+        .preset_manager
+        .presets
+        .iter()
+        .map(|preset| (preset, preset.voice.distance(&voice)))
+        .collect();
+    presets.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+
+    let mut message = "Closest presets:".to_string();
+    for (preset, distance) in presets.iter().take(ANALYZE_LEN) {
+        message.push_str(&format!(
+            "\n- **{}**, distance {:.3}",
+            preset.name, distance
+        ));
+        for (param, difference) in preset.voice.differences(&voice).iter().take(ANALYZE_LEN) {
+            if *difference == 0.0 {
+                break;
+            }
+            message.push_str(&format!(
+                "\n  - `{}` {}: {} instead of {} ({:+.0}% of its range)",
+                param.code,
+                param.name,
+                param.get(&voice),
+                param.get(&preset.voice),
+                difference * 100.0
+            ));
+        }
+    }
+
+    ctx.say(message).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, rename = "undo")]
+async fn voice_undo(ctx: Context<'_>) -> Result<(), Error> {
+    let author = ctx.author();
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+    if !voice_manager.undo(author.id.get()).await? {
+        ctx.say("Nothing to undo!").await?;
+        return Ok(());
+    }
+
+    let voice = voice_manager.get_user(author.id.get());
+    ctx.say(format!("```rust\n{:?}\n```", voice)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, rename = "redo")]
+async fn voice_redo(ctx: Context<'_>) -> Result<(), Error> {
+    let author = ctx.author();
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+    if !voice_manager.redo(author.id.get()).await? {
+        ctx.say("Nothing to redo!").await?;
+        return Ok(());
+    }
+
+    let voice = voice_manager.get_user(author.id.get());
+    ctx.say(format!("```rust\n{:?}\n```", voice)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, rename = "history")]
+async fn voice_history(
+    ctx: Context<'_>,
+    #[description = "Previous voice to restore, 1 being the most recent"]
+    #[min = 1]
+    index: Option<usize>,
+) -> Result<(), Error> {
+    let author = ctx.author();
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+    let history = voice_manager.history(author.id.get());
+
+    if let Some(index) = index {
+        let Some(voice) = history.iter().rev().nth(index - 1) else {
+            ctx.say("Invalid history index!").await?;
+            return Ok(());
+        };
+
+        match voice.clone() {
+            Some(voice) => voice_manager.set(author.id.get(), &voice).await?,
+            None => voice_manager.remove(author.id.get()).await?,
+        }
+
+        let voice = voice_manager.get_user(author.id.get());
+        ctx.say(format!("```rust\n{:?}\n```", voice)).await?;
+        return Ok(());
+    }
+
+    if history.is_empty() {
+        ctx.say("No previous voices!").await?;
+        return Ok(());
+    }
+
+    let mut message = "Previous voices, most recent first:".to_string();
+    for (index, voice) in history.iter().rev().enumerate() {
+        let description = match voice {
+            Some(voice) => {
+                let closest = ctx
+                    .data()
+                    .preset_manager
+                    .presets
+                    .iter()
+                    .min_by(|a, b| a.voice.distance(voice).total_cmp(&b.voice.distance(voice)));
+                match closest {
+                    Some(preset) => format!("`{}`, closest to {}", voice.to_code(), preset.name),
+                    None => format!("`{}`", voice.to_code()),
+                }
+            }
+            None => "Default voice".to_string(),
+        };
+        message.push_str(&format!("\n{}. {}", index + 1, description));
+    }
+
+    ctx.say(message).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, ephemeral, rename = "import")]
+async fn voice_import(
     ctx: Context<'_>,
-    #[description = "0-1 -- Sex 1 (male) or 0 (female)"] sx: Option<u8>,
-    #[description = "65-145 % Head size"] hs: Option<u8>,
-    #[description = "2000-4650 Hz Fourth formant frequency"] f4: Option<u16>,
-    #[description = "2500-4950 Hz Fifth formant frequency"] f5: Option<u16>,
-    #[description = "100-2048 Hz Fourth formant bandwidth"] b4: Option<u16>,
-    #[description = "100-2048 Hz Fifth formant bandwidth"] b5: Option<u16>,
-    #[description = "0-72 dB Breathiness"] br: Option<u8>,
-    #[description = "0-100 % Lax breathiness"] lx: Option<u8>,
-    #[description = "0-100 % Smoothness (high frequency attenuation)"] sm: Option<u8>,
-    #[description = "0-100 % Richness"] ri: Option<u8>,
-    #[description = "0-100 -- Number of fixed samplings of glottal pulse open phase"] nf: Option<
-        u8,
-    >,
-    #[description = "0-100 % Laryngealization"] la: Option<u8>,
-    #[description = "0-40 Hz Baseline fall"] bf: Option<u8>,
-    #[description = "2-100 Hz Hat rise"] hr: Option<u8>,
-    #[description = "1-100 Hz Stress rise"] sr: Option<u8>,
-    #[description = "0-100 % Assertiveness"] as_: Option<u8>,
-    #[description = "0-100 % Quickness"] qu: Option<u8>,
-    #[description = "50-350 Hz Average pitch"] ap: Option<u16>,
-    #[description = "0-250 % Pitch range"] pr: Option<u8>,
-    #[description = "0-86 dB Gain of voicing source"] gv: Option<u8>,
-    #[description = "0-86 dB Gain of aspiration source"] gh: Option<u8>,
-    #[description = "0-86 dB Gain of frication source"] gf: Option<u8>,
-    #[description = "0-86 dB Gain of nasalization"] gn: Option<u8>,
-    // #[description = "0-86 dB Gain of first formant resonator"] g1: Option<u8>,
-    // #[description = "0-86 dB Gain of second formant resonator"] g2: Option<u8>,
-    // #[description = "0-86 dB Gain of third formant resonator"] g3: Option<u8>,
-    // #[description = "0-86 dB Gain of fourth formant resonator"] g4: Option<u8>,
-    // #[description = "0-86 dB Gain of fifth formant resonator (replaces lo)"] g5: Option<u8>,
+    #[description = "Voice string, e.g. \"[:nv][:dv ap 90 pr 200]\""] voice: String,
+    #[description = "Preset the string is applied on, defaults to your current voice"]
+    #[autocomplete = "autocomplete_voice_preset"]
+    base: Option<String>,
 ) -> Result<(), Error> {
     let author = ctx.author();
 
     let mut voice_manager = ctx.data().voice_manager.lock().await;
-    let voice = voice_manager.get(author.id.get());
-
-    let voice = dectalk::DECtalkVoice {
-        sx: sx.unwrap_or(voice.sx),
-        hs: hs.unwrap_or(voice.hs),
-        f4: f4.unwrap_or(voice.f4),
-        f5: f5.unwrap_or(voice.f5),
-        b4: b4.unwrap_or(voice.b4),
-        b5: b5.unwrap_or(voice.b5),
-        br: br.unwrap_or(voice.br),
-        lx: lx.unwrap_or(voice.lx),
-        sm: sm.unwrap_or(voice.sm),
-        ri: ri.unwrap_or(voice.ri),
-        nf: nf.unwrap_or(voice.nf),
-        la: la.unwrap_or(voice.la),
-        bf: bf.unwrap_or(voice.bf),
-        hr: hr.unwrap_or(voice.hr),
-        sr: sr.unwrap_or(voice.sr),
-        as_: as_.unwrap_or(voice.as_),
-        qu: qu.unwrap_or(voice.qu),
-        ap: ap.unwrap_or(voice.ap),
-        pr: pr.unwrap_or(voice.pr),
-        gv: gv.unwrap_or(voice.gv),
-        gh: gh.unwrap_or(voice.gh),
-        gf: gf.unwrap_or(voice.gf),
-        gn: gn.unwrap_or(voice.gn),
-        // g1: g1.unwrap_or(voice.g1),
-        // g2: g2.unwrap_or(voice.g2),
-        // g3: g3.unwrap_or(voice.g3),
-        // g4: g4.unwrap_or(voice.g4),
-        // g5: g5.unwrap_or(voice.g5),
-        g1: voice.g1,
-        g2: voice.g2,
-        g3: voice.g3,
-        g4: voice.g4,
-        g5: voice.g5,
-    };
-
-    if !voice.validate() {
-        ctx.say("Invalid voice!").await?;
+    let mut imported = match base {
+        Some(base) => match ctx.data().preset_manager.get(&base) {
+            Some(base) => base.voice.clone(),
+            None => {
+                ctx.say("Invalid voice preset!").await?;
+                return Ok(());
+            }
+        },
+        None => voice_manager.get_user(author.id.get()).clone(),
+    };
+
+    if let Err(err) = imported.apply_dectalk_str(&voice) {
+        ctx.say(format!("Invalid voice string: {}", err)).await?;
         return Ok(());
     }
 
-    voice_manager.set(author.id.get(), &voice).await?;
-    ctx.say(format!("```rust\n{:?}\n```", voice)).await?;
+    if let Err(errors) = imported.validate() {
+        ctx.say(invalid_voice_message(&errors)).await?;
+        return Ok(());
+    }
+
+    voice_manager.set(author.id.get(), &imported).await?;
+    ctx.say(format!("```rust\n{:?}\n```", imported)).await?;
     Ok(())
 }
 
-async fn autocomplete_voice_preset<'a>(
-    _ctx: Context<'_>,
-    partial: &'a str,
-) -> impl Stream<Item = String> + 'a {
-    stream::iter(&[
-        "Paul", "Harry", "Frank", "Dennis", "Betty", "Ursula", "Wendy", "Rita", "Kit",
-    ])
-    .filter(move |name| future::ready(name.starts_with(partial)))
-    .map(|name| name.to_string())
+#[poise::command(slash_command, ephemeral, rename = "export")]
+async fn voice_export(ctx: Context<'_>) -> Result<(), Error> {
+    let author = ctx.author();
+
+    let voice_manager = ctx.data().voice_manager.lock().await;
+    let voice = voice_manager.get_user(author.id.get());
+
+    ctx.say(format!("```\n{}\n```", voice.to_dectalk_string()))
+        .await?;
+    Ok(())
+}
+
+/// Longest autocomplete choice name or value Discord accepts
+const CHOICE_LEN: usize = 100;
+
+/// Longest message Discord accepts
+const MESSAGE_LEN: usize = 2000;
+
+async fn autocomplete_voice_preset(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<serenity::AutocompleteChoice> {
+    ctx.data()
+        .preset_manager
+        .presets
+        .iter()
+        // Discord rejects every choice when one is too long, a name that
+        // doesn't fit can't be picked.
+        .filter(|preset| preset.matches(partial) && preset.name.chars().count() <= CHOICE_LEN)
+        .take(25)
+        .map(|preset| {
+            let label = if preset.description.is_empty() {
+                preset.name.clone()
+            } else {
+                format!("{}: {}", preset.name, preset.description)
+            };
+            serenity::AutocompleteChoice::new(
+                utils::truncate(&label, CHOICE_LEN),
+                preset.name.clone(),
+            )
+        })
+        .collect()
 }
 
 #[poise::command(slash_command, ephemeral)]
@@ -129,26 +903,57 @@ async fn preset(
 ) -> Result<(), Error> {
     let author = ctx.author();
 
-    let voice = match voice.as_str() {
-        "Paul" => dectalk::PAUL_VOICE,
-        "Harry" => dectalk::HARRY_VOICE,
-        "Frank" => dectalk::FRANK_VOICE,
-        "Dennis" => dectalk::DENNIS_VOICE,
-        "Betty" => dectalk::BETTY_VOICE,
-        "Ursula" => dectalk::URSULA_VOICE,
-        "Wendy" => dectalk::WENDY_VOICE,
-        "Rita" => dectalk::RITA_VOICE,
-        "Kit" => dectalk::KIT_VOICE,
-        _ => {
-            ctx.say("Invalid voice preset!").await?;
-            return Ok(());
-        }
+    let Some(preset) = ctx.data().preset_manager.get(&voice) else {
+        ctx.say("Invalid voice preset!").await?;
+        return Ok(());
     };
 
     let mut voice_manager = ctx.data().voice_manager.lock().await;
-    voice_manager.set(author.id.get(), &voice).await?;
+    voice_manager.set(author.id.get(), &preset.voice).await?;
 
-    ctx.say(format!("```rust\n{:?}\n```", voice)).await?;
+    ctx.say(format!("```rust\n{:?}\n```", preset.voice)).await?;
+    Ok(())
+}
+
+/// Longest line `/presets` shows for a single preset
+const PRESET_LINE_LEN: usize = 200;
+
+#[poise::command(slash_command, ephemeral)]
+async fn presets(
+    ctx: Context<'_>,
+    #[description = "Only list presets with this tag, e.g. \"female\""] tag: Option<String>,
+) -> Result<(), Error> {
+    let presets: Vec<_> = ctx
+        .data()
+        .preset_manager
+        .presets
+        .iter()
+        .filter(|preset| tag.as_ref().is_none_or(|tag| preset.has_tag(tag)))
+        .collect();
+
+    let mut message = String::new();
+    for (index, preset) in presets.iter().enumerate() {
+        let mut line = format!("- **{}**", preset.name);
+        if !preset.description.is_empty() {
+            line.push_str(&format!(": {}", preset.description));
+        }
+        if !preset.tags.is_empty() {
+            line.push_str(&format!(" `{}`", preset.tags.join(", ")));
+        }
+        let line = utils::truncate(&line, PRESET_LINE_LEN) + "\n";
+
+        // Leave room for the note on what was left out.
+        if message.chars().count() + line.chars().count() > MESSAGE_LEN - 32 {
+            message.push_str(&format!("…and {} more", presets.len() - index));
+            break;
+        }
+        message.push_str(&line);
+    }
+
+    if message.is_empty() {
+        message = "No presets found!".to_string();
+    }
+    ctx.say(message).await?;
     Ok(())
 }
 
@@ -159,25 +964,302 @@ async fn reset(ctx: Context<'_>) -> Result<(), Error> {
     let mut voice_manager = ctx.data().voice_manager.lock().await;
     voice_manager.remove(author.id.get()).await?;
 
-    let voice = voice_manager.get(author.id.get());
+    let voice = voice_manager.get_user(author.id.get());
     ctx.say(format!("```rust\n{:?}\n```", voice)).await?;
     Ok(())
 }
 
+#[poise::command(slash_command, ephemeral)]
+async fn rate(
+    ctx: Context<'_>,
+    #[description = "75-600 wpm Speaking rate"]
+    #[min = 75]
+    #[max = 600]
+    rate: Option<u16>,
+) -> Result<(), Error> {
+    let author = ctx.author();
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+    let rate = if let Some(rate) = rate {
+        if !(dectalk::MIN_RATE..=dectalk::MAX_RATE).contains(&rate) {
+            ctx.say("Invalid rate!").await?;
+            return Ok(());
+        }
+
+        voice_manager.set_rate(author.id.get(), rate).await?;
+        rate
+    } else {
+        voice_manager.get_rate(author.id.get())
+    };
+
+    ctx.say(format!("Rate: `{}` wpm", rate)).await?;
+    Ok(())
+}
+
+async fn autocomplete_language(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<serenity::AutocompleteChoice> {
+    let language_manager = ctx.data().language_manager.lock().await;
+    let partial = partial.to_lowercase();
+
+    let mut choices = vec![("Default".to_string(), "default")];
+    choices.extend(language_manager.installed.iter().map(|language| {
+        (
+            format!("{} ({})", language.name(), language.code()),
+            language.code(),
+        )
+    }));
+
+    choices
+        .into_iter()
+        .filter(|(name, _)| name.to_lowercase().contains(&partial))
+        .map(|(name, value)| serenity::AutocompleteChoice::new(name, value))
+        .collect()
+}
+
+fn language_description(language: Option<dectalk::Language>) -> String {
+    match language {
+        Some(language) => format!("Language: `{}`", language.name()),
+        None => "Language: `Default`".to_string(),
+    }
+}
+
+// Parses a language option, `Ok(None)` is "default" and `Err` an unknown or
+// uninstalled language.
+fn parse_language(
+    language_manager: &LanguageManager,
+    language: &str,
+) -> Result<Option<dectalk::Language>, ()> {
+    if language.eq_ignore_ascii_case("default") {
+        return Ok(None);
+    }
+
+    match dectalk::Language::from_code(language) {
+        Some(language) if language_manager.installed.contains(&language) => Ok(Some(language)),
+        _ => Err(()),
+    }
+}
+
+#[poise::command(slash_command, ephemeral)]
+async fn language(
+    ctx: Context<'_>,
+    #[description = "Language to speak your messages in"]
+    #[autocomplete = "autocomplete_language"]
+    language: Option<String>,
+) -> Result<(), Error> {
+    let author = ctx.author();
+
+    let mut language_manager = ctx.data().language_manager.lock().await;
+    let language = if let Some(language) = language {
+        let Ok(language) = parse_language(&language_manager, &language) else {
+            ctx.say("Invalid language!").await?;
+            return Ok(());
+        };
+
+        language_manager.set_user(author.id.get(), language).await?;
+        language
+    } else {
+        language_manager.get_user(author.id.get())
+    };
+
+    ctx.say(language_description(language)).await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    ephemeral,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+async fn guildlanguage(
+    ctx: Context<'_>,
+    #[description = "Language for members who haven't picked one"]
+    #[autocomplete = "autocomplete_language"]
+    language: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let mut language_manager = ctx.data().language_manager.lock().await;
+    let language = if let Some(language) = language {
+        let Ok(language) = parse_language(&language_manager, &language) else {
+            ctx.say("Invalid language!").await?;
+            return Ok(());
+        };
+
+        language_manager.set_guild(guild_id.get(), language).await?;
+        language
+    } else {
+        language_manager.get_guild(guild_id.get())
+    };
+
+    ctx.say(language_description(language)).await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    ephemeral,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+async fn guildvoice(
+    ctx: Context<'_>,
+    #[description = "Preset for members who haven't set a voice"]
+    #[autocomplete = "autocomplete_voice_preset"]
+    preset: Option<String>,
+    #[description = "Voice string, e.g. \"[:nv][:dv ap 90 pr 200]\", applied on the preset"]
+    voice: Option<String>,
+    #[description = "Go back to Paul"] reset: Option<bool>,
+    #[description = "Give members without a voice a distinct one, derived from their user ID"]
+    auto: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let mut voice_manager = ctx.data().voice_manager.lock().await;
+
+    if reset == Some(true) {
+        voice_manager
+            .set_guild_default(guild_id.get(), None)
+            .await?;
+    } else if preset.is_some() || voice.is_some() {
+        let mut default = match preset {
+            Some(preset) => match ctx.data().preset_manager.get(&preset) {
+                Some(preset) => preset.voice.clone(),
+                None => {
+                    ctx.say("Invalid voice preset!").await?;
+                    return Ok(());
+                }
+            },
+            None => dectalk::DECtalkVoice::default(),
+        };
+
+        if let Some(voice) = voice
+            && let Err(err) = default.apply_dectalk_str(&voice)
+        {
+            ctx.say(format!("Invalid voice string: {}", err)).await?;
+            return Ok(());
+        }
+
+        if let Err(errors) = default.validate() {
+            ctx.say(invalid_voice_message(&errors)).await?;
+            return Ok(());
+        }
+
+        voice_manager
+            .set_guild_default(guild_id.get(), Some(&default))
+            .await?;
+    }
+
+    if let Some(auto) = auto {
+        voice_manager.set_auto_voice(guild_id.get(), auto).await?;
+    }
+
+    if voice_manager.get_auto_voice(guild_id.get()) {
+        ctx.say("Members without a voice get one derived from their user ID.")
+            .await?;
+        return Ok(());
+    }
+
+    let default = voice_manager
+        .get_guild_default(guild_id.get())
+        .unwrap_or(dectalk::default_voice());
+    ctx.say(format!("```rust\n{:?}\n```", default)).await?;
+    Ok(())
+}
+
+async fn speech_settings(
+    data: &Data,
+    guild_id: Option<u64>,
+    user_id: u64,
+) -> dectalk::DECtalkSettings {
+    let language = {
+        let language_manager = data.language_manager.lock().await;
+        language_manager.get(guild_id, user_id)
+    };
+
+    let voice_manager = data.voice_manager.lock().await;
+    dectalk::DECtalkSettings {
+        voice: voice_manager.get(guild_id, user_id),
+        rate: voice_manager.get_rate(user_id),
+        language,
+    }
+}
+
+// Strips the markup the guild doesn't allow (all of it outside guilds) and
+// caps the length, the way text is limited before it is spoken. Owners are
+// exempt. Returns whether the text was cut short.
+async fn restrict_text(
+    data: &Data,
+    guild_id: Option<serenity::GuildId>,
+    owner: bool,
+    text: &mut String,
+) -> bool {
+    if !owner {
+        let markup_manager = data.markup_manager.lock().await;
+        *text = match guild_id {
+            Some(guild_id) => {
+                let policy = markup_manager.get(guild_id.get());
+                dectalk::sanitize(text, |command| policy.allows(command))
+            }
+            None => dectalk::sanitize(text, |_| false),
+        };
+    }
+    *text = text.trim().to_string();
+
+    if owner || text.len() <= data.tts_len {
+        return false;
+    }
+
+    let mut end = data.tts_len;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    // Don't leave a command cut in half at the end.
+    if let Some(open) = text.rfind('[')
+        && !text[open..].contains(']')
+    {
+        text.truncate(open);
+    }
+    true
+}
+
 #[poise::command(slash_command)]
 async fn test(ctx: Context<'_>, text: String) -> Result<(), Error> {
     let author = ctx.author();
 
-    let voice_manager = ctx.data().voice_manager.lock().await;
-    let voice = voice_manager.get(author.id.get());
+    let settings = speech_settings(
+        ctx.data(),
+        ctx.guild_id().map(|guild_id| guild_id.get()),
+        author.id.get(),
+    )
+    .await;
 
-    let tts_bytes = tts(&text, voice, ctx.id()).await?;
+    let mut text = transliterate::transliterate(&text, ctx.data().tts_replacement.as_deref());
+    let owner = ctx.framework().options.owners.contains(&author.id);
+    let cut = restrict_text(ctx.data(), ctx.guild_id(), owner, &mut text).await;
 
-    ctx.send(
-        poise::CreateReply::default()
-            .attachment(serenity::CreateAttachment::bytes(tts_bytes, "tts.wav")),
-    )
-    .await?;
+    if text.is_empty() {
+        ctx.say("Nothing to say!").await?;
+        return Ok(());
+    }
+
+    let tts_bytes = tts(&text, &settings, ctx.id()).await?;
+
+    let mut reply = poise::CreateReply::default()
+        .attachment(serenity::CreateAttachment::bytes(tts_bytes, "tts.wav"));
+    if cut {
+        reply = reply.content(format!("⚠ Cut to {} characters", ctx.data().tts_len));
+    }
+    ctx.send(reply).await?;
     Ok(())
 }
 
@@ -233,6 +1315,137 @@ async fn prefix(ctx: Context<'_>, prefix: Option<String>) -> Result<(), Error> {
     Ok(())
 }
 
+#[poise::command(
+    slash_command,
+    ephemeral,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+async fn markup(
+    ctx: Context<'_>,
+    #[description = "How inline DECtalk commands in messages are handled"] mode: Option<MarkupMode>,
+    #[description = "Allowed command names, e.g. \"phoneme rate\""] whitelist: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = match ctx.guild_id() {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let mut markup_manager = ctx.data().markup_manager.lock().await;
+    let mut policy = markup_manager.get(guild_id.get()).clone();
+
+    if mode.is_some() || whitelist.is_some() {
+        if let Some(mode) = mode {
+            policy.mode = mode;
+        }
+        if let Some(whitelist) = whitelist {
+            policy.whitelist = whitelist
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .map(|command| command.trim_start_matches(':').to_ascii_lowercase())
+                .filter(|command| !command.is_empty())
+                .collect();
+        }
+        markup_manager.set(guild_id.get(), &policy).await?;
+    }
+
+    let MarkupPolicy { mode, whitelist } = policy;
+    ctx.say(format!(
+        "Markup: `{:?}`\nWhitelist: `{}`",
+        mode,
+        whitelist.into_iter().collect::<Vec<_>>().join(" ")
+    ))
+    .await?;
+    Ok(())
+}
+
+async fn guild_call(ctx: Context<'_>) -> Option<Arc<Mutex<songbird::Call>>> {
+    let guild_id = ctx.guild_id()?;
+    let manager = songbird::get(ctx.serenity_context())
+        .await
+        .expect("Songbird Voice client placed in at initialisation.");
+
+    manager.get(guild_id)
+}
+
+#[poise::command(
+    slash_command,
+    ephemeral,
+    guild_only,
+    required_permissions = "MUTE_MEMBERS"
+)]
+async fn skip(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(handler_lock) = guild_call(ctx).await else {
+        ctx.say("Not in a voice channel!").await?;
+        return Ok(());
+    };
+
+    let handler = handler_lock.lock().await;
+    let queue = handler.queue();
+    // The queue isn't behind the call lock, it can empty out at any moment.
+    let Some(playing) = queue.current() else {
+        ctx.say("Nothing is playing!").await?;
+        return Ok(());
+    };
+
+    // Every sentence of the current message is its own track, skip them all.
+    // Stopping the playing track moves the queue on to the next one.
+    let current = SpokenMessage::of(&playing).id;
+    let mut removed = remove_queued(queue, |_, id| id == current);
+    let left = queue.len().saturating_sub(1);
+    removed.push(playing);
+    stop_messages(&removed);
+
+    ctx.say(format!("Skipped, `{}` left in queue", left))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    ephemeral,
+    guild_only,
+    required_permissions = "MUTE_MEMBERS"
+)]
+async fn clear(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(handler_lock) = guild_call(ctx).await else {
+        ctx.say("Not in a voice channel!").await?;
+        return Ok(());
+    };
+
+    let handler = handler_lock.lock().await;
+
+    // Keep the current message playing to its end, only drop the messages
+    // waiting behind it.
+    let removed = remove_queued(handler.queue(), |current, id| id != current);
+    stop_messages(&removed);
+
+    ctx.say(format!("Cleared `{}` from queue", removed.len()))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    ephemeral,
+    guild_only,
+    required_permissions = "MUTE_MEMBERS"
+)]
+async fn stop(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(handler_lock) = guild_call(ctx).await else {
+        ctx.say("Not in a voice channel!").await?;
+        return Ok(());
+    };
+
+    let handler = handler_lock.lock().await;
+    let queue = handler.queue();
+    let stopped = queue.len();
+    stop_messages(&queue.current_queue());
+    queue.stop();
+
+    ctx.say(format!("Stopped `{}` from queue", stopped)).await?;
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() {
     env_logger::init();
@@ -250,9 +1463,28 @@ async fn main() {
         owners.insert(serenity::UserId::new(owner.parse().expect("invalid owner")));
     }
 
+    let mut voice = voice();
+    describe_voice_params(&mut voice);
+
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![preset(), voice(), reset(), test(), muted(), prefix()],
+            commands: vec![
+                preset(),
+                presets(),
+                voice,
+                rate(),
+                language(),
+                reset(),
+                test(),
+                muted(),
+                prefix(),
+                markup(),
+                guildlanguage(),
+                guildvoice(),
+                skip(),
+                clear(),
+                stop(),
+            ],
             event_handler: |ctx, event, framework, data| {
                 Box::pin(event_handler(ctx, event, framework, data))
             },
@@ -263,6 +1495,29 @@ async fn main() {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
 
+                let mut pool_config = dectalk::DECtalkPoolConfig::default();
+                if let Ok(say_path) = env::var("DECTALK_SAY") {
+                    pool_config.say_path = say_path.into();
+                }
+                if let Ok(max_concurrency) = env::var("TTS_CONCURRENCY") {
+                    pool_config.max_concurrency =
+                        max_concurrency.parse().expect("invalid TTS_CONCURRENCY");
+                }
+                // Keep a warm worker for every utterance that can run at once.
+                pool_config.workers = match env::var("TTS_WORKERS") {
+                    Ok(workers) => workers.parse().expect("invalid TTS_WORKERS"),
+                    Err(_) => pool_config.max_concurrency,
+                };
+                if let Ok(timeout) = env::var("TTS_TIMEOUT") {
+                    pool_config.timeout =
+                        Duration::from_secs_f32(timeout.parse().expect("invalid TTS_TIMEOUT"));
+                }
+                if let Ok(max_output) = env::var("TTS_MAX_OUTPUT") {
+                    pool_config.max_output = max_output.parse().expect("invalid TTS_MAX_OUTPUT");
+                }
+                let installed_languages = dectalk::installed_languages(&pool_config.say_path).await;
+                dectalk::init_pool(pool_config);
+
                 let mut voice_manager = VoiceManager::new();
                 if voice_manager.can_load().await {
                     voice_manager.load().await?;
@@ -280,10 +1535,29 @@ async fn main() {
                     prefix_manager.load().await?;
                 }
 
+                let mut markup_manager = MarkupManager::new();
+                if markup_manager.can_load().await {
+                    markup_manager.load().await?;
+                }
+
+                let mut language_manager = LanguageManager::new(installed_languages);
+                if language_manager.can_load().await {
+                    language_manager.load().await?;
+                }
+
+                let mut preset_manager = PresetManager::new();
+                if preset_manager.can_load().await {
+                    preset_manager.load().await?;
+                }
+
                 Ok(Data {
                     voice_manager: Arc::new(Mutex::new(voice_manager)),
                     mute_manager: Arc::new(Mutex::new(mute_manager)),
                     prefix_manager: Arc::new(Mutex::new(prefix_manager)),
+                    markup_manager: Arc::new(Mutex::new(markup_manager)),
+                    language_manager: Arc::new(Mutex::new(language_manager)),
+                    preset_manager,
+                    speech_locks: Mutex::new(HashMap::new()),
                     tts_len: env::var("TTS_LEN")
                         .expect("missing TTS_LEN")
                         .parse::<usize>()
@@ -292,6 +1566,10 @@ async fn main() {
                         .expect("missing TTS_PEAK")
                         .parse::<f32>()
                         .expect("invalid TTS_PEAK"),
+                    tts_replacement: env::var("TTS_REPLACEMENT").ok().inspect(|replacement| {
+                        // It is spoken as is, DECtalk only reads ASCII.
+                        assert!(replacement.is_ascii(), "invalid TTS_REPLACEMENT");
+                    }),
                 })
             })
         })
@@ -374,16 +1652,16 @@ async fn message_event_handler(
 
     text = utils::replace_links(&text);
     text = utils::replace_discord_emojis(&text);
-    text = text.trim().to_string();
+    text = transliterate::transliterate(&text, data.tts_replacement.as_deref());
+
+    let owner = framework.options.owners.contains(&msg.author.id);
+    let cut = restrict_text(data, Some(guild_id), owner, &mut text).await;
 
     if text.len() == 0 {
         return Ok(());
     }
 
-    if text.len() > framework.user_data.tts_len
-        && !framework.options.owners.contains(&msg.author.id)
-    {
-        text = text[..framework.user_data.tts_len].to_string();
+    if cut {
         msg.react(ctx, '⚠').await?;
     }
 
@@ -391,26 +1669,59 @@ async fn message_event_handler(
         .await
         .expect("Songbird Voice client placed in at initialisation.");
 
+    // Wait for earlier messages in the guild to be queued first.
+    let speech_lock = data
+        .speech_locks
+        .lock()
+        .await
+        .entry(guild_id)
+        .or_default()
+        .clone();
+    let _speaking = speech_lock.lock().await;
+
     let handler_lock = manager.get_or_insert(guild_id);
-    let mut handler = handler_lock.lock().await;
-    handler.deafen(true).await?;
-    handler.join(msg.channel_id).await?.await?;
+    {
+        let mut handler = handler_lock.lock().await;
+        handler.deafen(true).await?;
+        handler.join(msg.channel_id).await?.await?;
+    }
 
-    let voice = {
-        let voice_manager = data.voice_manager.lock().await;
-        voice_manager.get(msg.author.id.get()).clone()
-    };
+    let settings = speech_settings(data, Some(guild_id.get()), msg.author.id.get()).await;
+    let message = Arc::new(SpokenMessage {
+        id: msg.id,
+        skipped: AtomicBool::new(false),
+    });
+
+    // Synthesize a few sentences ahead and queue each one as soon as it is
+    // ready, so playback starts after the first sentence instead of the last.
+    let mut chunks = stream::iter(dectalk::split_sentences(&text))
+        .map(|chunk| {
+            let settings = &settings;
+            async move { tts(&chunk, settings, msg.id.get()).await }
+        })
+        .buffered(TTS_LOOKAHEAD);
 
-    let mut tts_bytes = match tts(&text, &voice, msg.id.get()).await {
-        Ok(bytes) => bytes,
-        Err(err) => {
-            msg.react(ctx, '❌').await?;
-            return Err(Box::new(err));
+    while let Some(tts_bytes) = chunks.next().await {
+        if message.skipped.load(Ordering::Relaxed) {
+            break;
         }
-    };
 
-    tts_bytes = utils::normalize_wav(&tts_bytes, framework.user_data.tts_peak)?;
-    handler.play_input(Input::from(tts_bytes));
+        let tts_bytes = match tts_bytes {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                let reaction = match err {
+                    dectalk::DECtalkError::Timeout => '⌛',
+                    _ => '❌',
+                };
+                msg.react(ctx, reaction).await?;
+                return Err(Box::new(err));
+            }
+        };
+
+        let tts_bytes = utils::normalize_wav(&tts_bytes, framework.user_data.tts_peak)?;
+        let track = Track::new_with_data(Input::from(tts_bytes), message.clone());
+        handler_lock.lock().await.enqueue(track).await;
+    }
     Ok(())
 }
 
@@ -472,6 +1783,8 @@ async fn voice_state_update_event_handler(
     };
 
     if members.len() <= 1 {
+        stop_messages(&handler.queue().current_queue());
+        handler.queue().stop();
         handler.leave().await?;
     }
 
@@ -0,0 +1,95 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    io,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::fs;
+
+#[derive(Error, Debug)]
+pub enum MarkupManagerError {
+    #[error("IO error: {0}")]
+    IOError(#[from] io::Error),
+    #[error("Serde error: {0}")]
+    SerdeError(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, poise::ChoiceParameter)]
+pub enum MarkupMode {
+    #[name = "Strip all"]
+    Strip,
+    #[name = "Allow whitelist"]
+    Whitelist,
+    #[name = "Allow all"]
+    All,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarkupPolicy {
+    pub mode: MarkupMode,
+    pub whitelist: BTreeSet<String>,
+}
+
+impl MarkupPolicy {
+    pub fn allows(&self, command: &str) -> bool {
+        match self.mode {
+            MarkupMode::Strip => false,
+            MarkupMode::Whitelist => self.whitelist.contains(command),
+            MarkupMode::All => true,
+        }
+    }
+}
+
+impl Default for MarkupPolicy {
+    fn default() -> Self {
+        Self {
+            mode: MarkupMode::Whitelist,
+            whitelist: BTreeSet::from(["phoneme".to_string()]),
+        }
+    }
+}
+
+pub struct MarkupManager {
+    pub default_policy: MarkupPolicy,
+    pub policies: HashMap<u64, MarkupPolicy>,
+}
+
+impl MarkupManager {
+    pub fn new() -> Self {
+        Self {
+            default_policy: MarkupPolicy::default(),
+            policies: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, guild_id: u64) -> &MarkupPolicy {
+        self.policies.get(&guild_id).unwrap_or(&self.default_policy)
+    }
+
+    pub async fn set(
+        &mut self,
+        guild_id: u64,
+        policy: &MarkupPolicy,
+    ) -> Result<(), MarkupManagerError> {
+        self.policies.insert(guild_id, policy.clone());
+        self.save().await?;
+        Ok(())
+    }
+
+    pub async fn can_load(&self) -> bool {
+        fs::metadata("data/markup.json").await.is_ok()
+    }
+
+    pub async fn load(&mut self) -> Result<(), MarkupManagerError> {
+        let policies_string = fs::read_to_string("data/markup.json").await?;
+        self.policies = serde_json::from_str(&policies_string)?;
+        Ok(())
+    }
+
+    pub async fn save(&self) -> Result<(), MarkupManagerError> {
+        let policies_string = serde_json::to_string(&self.policies)?;
+        fs::write("data/markup.json", policies_string).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+use std::{io, path::Path, sync::OnceLock};
+
+use crate::dectalk::{DECtalkVoice, ParseVoiceError, VoiceFieldError};
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::fs;
+
+const PRESETS_DIR: &str = "data/presets";
+
+/// The built-in DECtalk speakers, a list of preset files
+const BUILTIN_PRESETS: &str = include_str!("presets.json");
+
+#[derive(Error, Debug)]
+pub enum PresetManagerError {
+    #[error("IO error: {0}")]
+    IOError(#[from] io::Error),
+    #[error("Serde error in {0}: {1}")]
+    SerdeError(String, serde_json::Error),
+    #[error("Invalid voice string in {0}: {1}")]
+    ParseError(String, ParseVoiceError),
+    #[error("Invalid voice in {0}: {errors}", errors = list_errors(.1))]
+    InvalidVoice(String, Vec<VoiceFieldError>),
+}
+
+fn list_errors(errors: &[VoiceFieldError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, Clone)]
+pub struct Preset {
+    pub name: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub voice: DECtalkVoice,
+}
+
+impl Preset {
+    pub fn matches(&self, partial: &str) -> bool {
+        let partial = partial.to_lowercase();
+        self.name.to_lowercase().starts_with(&partial)
+            || self.tags.iter().any(|tag| tag.to_lowercase() == partial)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+}
+
+/// A preset as written in `data/presets/*.json`. The voice is a voice
+/// string, e.g. `"[:nb][:dv ap 240]"`, applied on Paul.
+#[derive(Deserialize)]
+struct PresetFile {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    voice: String,
+}
+
+/// The built-in DECtalk speakers. Their voices set every parameter, so they
+/// don't depend on Paul, or on each other through `[:nX]`.
+pub fn builtin_presets() -> &'static [Preset] {
+    static PRESETS: OnceLock<Vec<Preset>> = OnceLock::new();
+    PRESETS.get_or_init(|| {
+        let files: Vec<PresetFile> =
+            serde_json::from_str(BUILTIN_PRESETS).expect("invalid built-in presets");
+        files
+            .into_iter()
+            .map(|file| {
+                parse_preset("built-in presets", file, DECtalkVoice::from_design)
+                    .expect("invalid built-in preset")
+            })
+            .collect()
+    })
+}
+
+pub struct PresetManager {
+    pub presets: Vec<Preset>,
+}
+
+impl PresetManager {
+    /// A registry holding the built-in DECtalk speakers
+    pub fn new() -> Self {
+        Self {
+            presets: builtin_presets().to_vec(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Preset> {
+        self.presets
+            .iter()
+            .find(|preset| preset.name.eq_ignore_ascii_case(name))
+    }
+
+    pub async fn can_load(&self) -> bool {
+        fs::metadata(PRESETS_DIR).await.is_ok()
+    }
+
+    /// Adds every preset file in `data/presets`, in file name order. A file
+    /// using the name of an existing preset replaces it, invalid files are
+    /// logged and skipped.
+    pub async fn load(&mut self) -> Result<(), PresetManagerError> {
+        let mut paths = Vec::new();
+        let mut entries = fs::read_dir(PRESETS_DIR).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        for path in paths {
+            let preset = match load_preset(&path).await {
+                Ok(preset) => preset,
+                Err(err) => {
+                    log::warn!("Skipping preset {}: {}", path.display(), err);
+                    continue;
+                }
+            };
+            match self
+                .presets
+                .iter_mut()
+                .find(|p| p.name.eq_ignore_ascii_case(&preset.name))
+            {
+                Some(existing) => *existing = preset,
+                None => self.presets.push(preset),
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn load_preset(path: &Path) -> Result<Preset, PresetManagerError> {
+    let file_name = path.display().to_string();
+
+    let preset_string = fs::read_to_string(path).await?;
+    let file: PresetFile = serde_json::from_str(&preset_string)
+        .map_err(|err| PresetManagerError::SerdeError(file_name.clone(), err))?;
+
+    parse_preset(&file_name, file, str::parse)
+}
+
+// Turns a preset file into a preset, `parse` reads its voice string.
+fn parse_preset(
+    file_name: &str,
+    file: PresetFile,
+    parse: fn(&str) -> Result<DECtalkVoice, ParseVoiceError>,
+) -> Result<Preset, PresetManagerError> {
+    let voice = parse(&file.voice)
+        .map_err(|err| PresetManagerError::ParseError(file_name.to_string(), err))?;
+    voice
+        .validate()
+        .map_err(|errors| PresetManagerError::InvalidVoice(file_name.to_string(), errors))?;
+
+    Ok(Preset {
+        name: file.name,
+        description: file.description,
+        tags: file.tags,
+        voice,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dectalk::{self, markup::Speaker};
+
+    #[test]
+    fn builtin_presets_are_valid() {
+        assert!(!builtin_presets().is_empty());
+        for preset in builtin_presets() {
+            assert!(preset.voice.validate().is_ok(), "{}", preset.name);
+        }
+    }
+
+    #[test]
+    fn every_speaker_has_a_builtin_preset() {
+        for speaker in Speaker::ALL {
+            let voice = dectalk::speaker_voice(speaker);
+            assert_eq!(voice.is_some(), speaker != Speaker::Val, "{:?}", speaker);
+        }
+    }
+
+    #[test]
+    fn speaker_command_selects_builtin_voice() {
+        let voice: DECtalkVoice = "[:nh]".parse().unwrap();
+        let harry = builtin_presets()
+            .iter()
+            .find(|p| p.name == "Harry")
+            .unwrap();
+        assert_eq!(voice.ap, harry.voice.ap);
+        assert_eq!(voice.hs, harry.voice.hs);
+    }
+}
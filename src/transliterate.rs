@@ -0,0 +1,224 @@
+/// Rewrites `text` as plain ASCII so it can be spoken by DECtalk.
+///
+/// Accented Latin letters lose their accents, Greek and Cyrillic are
+/// romanized, typographic punctuation becomes its ASCII counterpart and
+/// common symbols are spelled out. Everything else, emoji and other scripts
+/// included, goes through the `deunicode` tables. Anything left over is
+/// dropped, or replaced by `replacement` when one is given (once per run of
+/// unknown characters, so a line of them doesn't become a wall of the same
+/// word).
+pub fn transliterate(text: &str, replacement: Option<&str>) -> String {
+    let mut transliterated = String::with_capacity(text.len());
+    let mut unknown = false;
+
+    for c in text.chars() {
+        if c.is_ascii() {
+            transliterated.push(c);
+            unknown = false;
+            continue;
+        }
+
+        if is_ignorable(c) {
+            continue;
+        }
+
+        if let Some(ascii) = map_char(c) {
+            transliterated.push_str(&ascii);
+            unknown = false;
+        } else if c.is_whitespace() {
+            transliterated.push(' ');
+            unknown = false;
+        } else if !unknown {
+            if let Some(replacement) = replacement {
+                transliterated.push(' ');
+                transliterated.push_str(replacement);
+                transliterated.push(' ');
+            }
+            unknown = true;
+        }
+    }
+
+    transliterated
+}
+
+// Characters that only modify their neighbours: combining accents, emoji
+// variation selectors, zero width joiners and skin tone modifiers.
+fn is_ignorable(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{200B}'..='\u{200F}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{1F3FB}'..='\u{1F3FF}'
+    )
+}
+
+fn map_char(c: char) -> Option<String> {
+    if let Some(ascii) = map_symbol(c) {
+        return Some(ascii.to_string());
+    }
+
+    // Letter tables only hold lowercase, uppercase input is capitalized back.
+    let lower = c.to_lowercase().next()?;
+    let Some(ascii) = map_latin(lower)
+        .or_else(|| map_greek(lower))
+        .or_else(|| map_cyrillic(lower))
+    else {
+        return map_deunicode(c);
+    };
+
+    if lower == c {
+        return Some(ascii.to_string());
+    }
+
+    let mut chars = ascii.chars();
+    Some(match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    })
+}
+
+// Symbols and emoji come out as names ("fire"), spaced so they don't run
+// into the surrounding words.
+fn map_deunicode(c: char) -> Option<String> {
+    let ascii = deunicode::deunicode_char(c)?.trim();
+    if ascii.is_empty() || !ascii.is_ascii() {
+        return None;
+    }
+
+    if c.is_alphanumeric() {
+        Some(ascii.to_string() + " ")
+    } else {
+        Some(format!(" {} ", ascii))
+    }
+}
+
+fn map_latin(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĳ' => "ij",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ș' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' | 'ț' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    })
+}
+
+fn map_greek(c: char) -> Option<&'static str> {
+    Some(match c {
+        'α' | 'ά' => "a",
+        'β' => "v",
+        'γ' => "g",
+        'δ' => "d",
+        'ε' | 'έ' => "e",
+        'ζ' => "z",
+        'η' | 'ή' => "i",
+        'θ' => "th",
+        'ι' | 'ί' | 'ϊ' | 'ΐ' => "i",
+        'κ' => "k",
+        'λ' => "l",
+        'μ' => "m",
+        'ν' => "n",
+        'ξ' => "x",
+        'ο' | 'ό' => "o",
+        'π' => "p",
+        'ρ' => "r",
+        'σ' | 'ς' => "s",
+        'τ' => "t",
+        'υ' | 'ύ' | 'ϋ' | 'ΰ' => "y",
+        'φ' => "f",
+        'χ' => "ch",
+        'ψ' => "ps",
+        'ω' | 'ώ' => "o",
+        _ => return None,
+    })
+}
+
+fn map_cyrillic(c: char) -> Option<&'static str> {
+    Some(match c {
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' | 'ґ' => "g",
+        'д' => "d",
+        'е' | 'э' => "e",
+        'ё' => "yo",
+        'є' => "ye",
+        'ж' => "zh",
+        'з' => "z",
+        'и' | 'і' => "i",
+        'ї' => "yi",
+        'й' => "y",
+        'к' => "k",
+        'л' => "l",
+        'м' => "m",
+        'н' => "n",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'у' => "u",
+        'ф' => "f",
+        'х' => "kh",
+        'ц' => "ts",
+        'ч' => "ch",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' | 'ь' => "",
+        'ы' => "y",
+        'ю' => "yu",
+        'я' => "ya",
+        _ => return None,
+    })
+}
+
+fn map_symbol(c: char) -> Option<&'static str> {
+    Some(match c {
+        '‘' | '’' | '‚' | '‛' | '′' | '‹' | '›' => "'",
+        '“' | '”' | '„' | '‟' | '″' | '«' | '»' => "\"",
+        '‐' | '‑' | '‒' | '–' | '—' | '―' | '−' => "-",
+        '…' => "...",
+        '•' | '·' => " ",
+        '¡' => "!",
+        '¿' => "?",
+        '€' => " euro ",
+        '£' => " pound ",
+        '¥' => " yen ",
+        '¢' => " cent ",
+        '©' => " copyright ",
+        '®' => " registered ",
+        '™' => " trademark ",
+        '°' => " degrees ",
+        '±' => " plus or minus ",
+        '×' => " times ",
+        '÷' => " divided by ",
+        '½' => " one half ",
+        '¼' => " one quarter ",
+        '¾' => " three quarters ",
+        '²' => "2",
+        '³' => "3",
+        'µ' => " micro ",
+        '→' => " to ",
+        '←' => " from ",
+        _ => return None,
+    })
+}
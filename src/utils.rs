@@ -50,3 +50,37 @@ pub fn replace_links(text: &str) -> String {
 pub fn replace_discord_emojis(text: &str) -> String {
     EMOJI_REGEX.replace_all(text, "$1").to_string()
 }
+
+/// Cuts `text` to at most `max` characters, ending in "…" when it was cut
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// SplitMix64, a tiny PRNG. Fine for picking voices, not for anything that
+/// has to be unpredictable.
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Number in `min..=max`
+    pub fn range(&mut self, min: i64, max: i64) -> i64 {
+        let span = (max - min) as u64 + 1;
+        min + (self.next_u64() % span) as i64
+    }
+}
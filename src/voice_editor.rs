@@ -0,0 +1,456 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use poise::serenity_prelude as serenity;
+
+use crate::{
+    Context, Error,
+    dectalk::{self, DECtalkVoice, VOICE_PARAMS, VoiceParam},
+    invalid_voice_message, speech_settings,
+    utils::SplitMix64,
+};
+
+/// How long the editor waits for the next click before giving up
+const EDITOR_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+const PREVIEW_TEXT: &str = "This is how your voice sounds with these settings.";
+
+// A select menu holds at most 25 options, so the parameters are spread over
+// two: the voice source and formants, then prosody and gains.
+const SELECT_SPLIT: usize = 12;
+
+enum Action {
+    Select(usize),
+    Nudge(i32),
+    Preview,
+    Save,
+    Cancel,
+}
+
+impl Action {
+    fn parse(id: &str, data: &serenity::ComponentInteractionDataKind) -> Option<Self> {
+        Some(match id {
+            "select:0" | "select:1" => {
+                let serenity::ComponentInteractionDataKind::StringSelect { values } = data else {
+                    return None;
+                };
+                let code = values.first()?;
+                Action::Select(VOICE_PARAMS.iter().position(|param| param.code == code)?)
+            }
+            "preview" => Action::Preview,
+            "save" => Action::Save,
+            "cancel" => Action::Cancel,
+            id => Action::Nudge(id.strip_prefix("nudge:")?.parse().ok()?),
+        })
+    }
+}
+
+// Smallest nudge, about 2% of the parameter's range.
+fn step(param: &VoiceParam) -> i32 {
+    (i32::from(param.max - param.min) / 50).max(1)
+}
+
+fn nudge(voice: &mut DECtalkVoice, param: &VoiceParam, amount: i32) -> Result<(), Error> {
+    let value =
+        (i32::from(param.get(voice)) + amount).clamp(i32::from(param.min), i32::from(param.max));
+    param.set(voice, value as u32)?;
+    Ok(())
+}
+
+fn content(voice: &DECtalkVoice, param: &VoiceParam) -> String {
+    format!(
+        "Editing `{}` {}: **{}** ({})\n{}\n```\n{}\n```",
+        param.code,
+        param.name,
+        param.get(voice),
+        param.range(),
+        param.description,
+        voice.to_dectalk_string()
+    )
+}
+
+fn components(prefix: &str, selected: usize) -> Vec<serenity::CreateActionRow> {
+    let (first, second) = VOICE_PARAMS.split_at(SELECT_SPLIT);
+    let select = |id: usize, placeholder: &str, params: &[VoiceParam]| {
+        let options = params
+            .iter()
+            .map(|param| {
+                serenity::CreateSelectMenuOption::new(
+                    format!("{}: {}", param.code, param.name),
+                    param.code,
+                )
+                .description(param.range())
+                .default_selection(std::ptr::eq(param, &VOICE_PARAMS[selected]))
+            })
+            .collect();
+        serenity::CreateActionRow::SelectMenu(
+            serenity::CreateSelectMenu::new(
+                format!("{}:select:{}", prefix, id),
+                serenity::CreateSelectMenuKind::String { options },
+            )
+            .placeholder(placeholder),
+        )
+    };
+
+    let step = step(&VOICE_PARAMS[selected]);
+    let nudges = [-5 * step, -step, step, 5 * step]
+        .into_iter()
+        .map(|amount| {
+            serenity::CreateButton::new(format!("{}:nudge:{}", prefix, amount))
+                .label(format!("{:+}", amount))
+                .style(serenity::ButtonStyle::Secondary)
+        })
+        .collect();
+
+    let button = |id: &str, label: &str, style| {
+        serenity::CreateButton::new(format!("{}:{}", prefix, id))
+            .label(label)
+            .style(style)
+    };
+
+    vec![
+        select(0, "Source and formants", first),
+        select(1, "Prosody and gains", second),
+        serenity::CreateActionRow::Buttons(nudges),
+        serenity::CreateActionRow::Buttons(vec![
+            button("preview", "Preview", serenity::ButtonStyle::Primary),
+            button("save", "Save", serenity::ButtonStyle::Success),
+            button("cancel", "Cancel", serenity::ButtonStyle::Danger),
+        ]),
+    ]
+}
+
+// Speaks the preview text with `voice`, at the author's rate and language.
+async fn preview(
+    ctx: Context<'_>,
+    voice: &DECtalkVoice,
+) -> Result<serenity::CreateAttachment, Error> {
+    let mut settings = speech_settings(
+        ctx.data(),
+        ctx.guild_id().map(|id| id.get()),
+        ctx.author().id.get(),
+    )
+    .await;
+    settings.voice = voice.clone();
+
+    let tts_bytes = dectalk::tts(PREVIEW_TEXT, &settings, ctx.id()).await?;
+    Ok(serenity::CreateAttachment::bytes(tts_bytes, "preview.wav"))
+}
+
+/// Runs an ephemeral editor on the author's voice. Changes are kept in a
+/// draft until saved.
+pub async fn run(ctx: Context<'_>) -> Result<(), Error> {
+    let author = ctx.author();
+    let prefix = ctx.id().to_string();
+
+    let mut draft = {
+        let voice_manager = ctx.data().voice_manager.lock().await;
+        voice_manager.get_user(author.id.get()).clone()
+    };
+    let mut selected = VOICE_PARAMS
+        .iter()
+        .position(|param| param.code == "ap")
+        .unwrap_or_default();
+
+    let reply = ctx
+        .send(
+            poise::CreateReply::default()
+                .content(content(&draft, &VOICE_PARAMS[selected]))
+                .components(components(&prefix, selected)),
+        )
+        .await?;
+
+    loop {
+        let filter_prefix = format!("{}:", prefix);
+        let Some(press) = serenity::ComponentInteractionCollector::new(ctx)
+            .author_id(author.id)
+            .filter(move |press| press.data.custom_id.starts_with(&filter_prefix))
+            .timeout(EDITOR_TIMEOUT)
+            .await
+        else {
+            reply
+                .edit(
+                    ctx,
+                    poise::CreateReply::default()
+                        .content("Voice editor timed out, nothing was saved.")
+                        .components(vec![]),
+                )
+                .await?;
+            return Ok(());
+        };
+
+        let id = &press.data.custom_id[prefix.len() + 1..];
+        let Some(action) = Action::parse(id, &press.data.kind) else {
+            continue;
+        };
+
+        let mut warning = None;
+        let message = match action {
+            Action::Select(index) => {
+                selected = index;
+                None
+            }
+            Action::Nudge(amount) => {
+                nudge(&mut draft, &VOICE_PARAMS[selected], amount)?;
+                None
+            }
+            Action::Preview => {
+                press
+                    .create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                    .await?;
+
+                // A failed preview shouldn't close the editor and lose the draft.
+                let followup = serenity::CreateInteractionResponseFollowup::new().ephemeral(true);
+                let followup = match preview(ctx, &draft).await {
+                    Ok(file) => followup.add_file(file),
+                    Err(err) => followup.content(format!("Preview failed: {}", err)),
+                };
+                press.create_followup(ctx, followup).await?;
+                continue;
+            }
+            Action::Save => {
+                if let Err(errors) = draft.validate() {
+                    warning = Some(invalid_voice_message(&errors));
+                    None
+                } else {
+                    let mut voice_manager = ctx.data().voice_manager.lock().await;
+                    voice_manager.set(author.id.get(), &draft).await?;
+                    Some(format!("Voice saved!\n```rust\n{:?}\n```", draft))
+                }
+            }
+            Action::Cancel => Some("Voice editor closed, nothing was saved.".to_string()),
+        };
+
+        let response = match &message {
+            Some(message) => serenity::CreateInteractionResponseMessage::new()
+                .content(message)
+                .components(vec![]),
+            None => serenity::CreateInteractionResponseMessage::new()
+                .content(match warning {
+                    Some(warning) => format!(
+                        "{}\n\n{}",
+                        warning,
+                        content(&draft, &VOICE_PARAMS[selected])
+                    ),
+                    None => content(&draft, &VOICE_PARAMS[selected]),
+                })
+                .components(components(&prefix, selected)),
+        };
+        press
+            .create_response(
+                ctx,
+                serenity::CreateInteractionResponse::UpdateMessage(response),
+            )
+            .await?;
+
+        if message.is_some() {
+            return Ok(());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum RandomMode {
+    /// Close to the built-in voices
+    #[name = "natural"]
+    Natural,
+    /// Anything goes
+    #[name = "wild"]
+    Wild,
+}
+
+/// Range a parameter of a random voice has to stay in
+pub struct Limit {
+    param: &'static VoiceParam,
+    min: u16,
+    max: u16,
+}
+
+/// Parses the `keep` and `lock` options of `/voice random`: `keep` lists
+/// codes whose current value is kept (`sx ap`), `lock` lists codes with the
+/// range they are drawn from (`ap 80-120, pr 100`).
+pub fn parse_limits(
+    voice: &DECtalkVoice,
+    keep: Option<&str>,
+    lock: Option<&str>,
+) -> Result<Vec<Limit>, String> {
+    let tokens = |s: Option<&str>| -> Vec<String> {
+        s.unwrap_or_default()
+            .split([' ', ','])
+            .filter(|token| !token.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    let find =
+        |code: &str| VoiceParam::find(code).ok_or_else(|| format!("Unknown parameter `{}`", code));
+
+    let mut limits = Vec::new();
+    for code in tokens(keep) {
+        let param = find(&code)?;
+        let value = param.get(voice);
+        limits.push(Limit {
+            param,
+            min: value,
+            max: value,
+        });
+    }
+
+    for pair in tokens(lock).chunks(2) {
+        let [code, range] = pair else {
+            return Err(format!("Missing range for `{}`", pair[0]));
+        };
+        let param = find(code)?;
+        let (min, max) = range.split_once('-').unwrap_or((range, range));
+        let (Ok(min), Ok(max)) = (min.parse::<u16>(), max.parse::<u16>()) else {
+            return Err(format!("Invalid range `{}` for `{}`", range, code));
+        };
+
+        let (min, max) = (min.max(param.min), max.min(param.max));
+        if min > max {
+            return Err(format!(
+                "`{}` can't be locked to {}, it must be within {}",
+                param.code,
+                range,
+                param.range()
+            ));
+        }
+        limits.push(Limit { param, min, max });
+    }
+
+    Ok(limits)
+}
+
+fn random_voice(rng: &mut SplitMix64, mode: RandomMode, limits: &[Limit]) -> DECtalkVoice {
+    let mut voice = match mode {
+        RandomMode::Natural => dectalk::natural_voice(rng),
+        RandomMode::Wild => dectalk::wild_voice(rng),
+    };
+
+    for limit in limits {
+        let value = limit.param.get(&voice);
+        if value < limit.min || value > limit.max {
+            let value = rng.range(limit.min.into(), limit.max.into());
+            limit.param.set(&mut voice, value as u32).ok();
+        }
+    }
+    voice
+}
+
+fn random_content(voice: &DECtalkVoice) -> String {
+    format!("```\n{}\n```", voice.to_dectalk_string())
+}
+
+fn random_components(prefix: &str) -> Vec<serenity::CreateActionRow> {
+    let button = |id: &str, label: &str, style| {
+        serenity::CreateButton::new(format!("{}:{}", prefix, id))
+            .label(label)
+            .style(style)
+    };
+
+    vec![serenity::CreateActionRow::Buttons(vec![
+        button("reroll", "Reroll", serenity::ButtonStyle::Primary),
+        button("save", "Save", serenity::ButtonStyle::Success),
+        button("cancel", "Cancel", serenity::ButtonStyle::Danger),
+    ])]
+}
+
+/// Generates random voices with a preview until the author saves one.
+pub async fn random(ctx: Context<'_>, mode: RandomMode, limits: Vec<Limit>) -> Result<(), Error> {
+    let author = ctx.author();
+    let prefix = ctx.id().to_string();
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or_default();
+    let mut rng = SplitMix64::new(nanos ^ ctx.id());
+
+    ctx.defer_ephemeral().await?;
+
+    let mut voice = random_voice(&mut rng, mode, &limits);
+    let mut create = poise::CreateReply::default()
+        .content(random_content(&voice))
+        .components(random_components(&prefix));
+    // Without a preview the voice can still be saved or rerolled.
+    let mut error = None;
+    match preview(ctx, &voice).await {
+        Ok(file) => create = create.attachment(file),
+        Err(err) => error = Some(err),
+    }
+    let reply = ctx.send(create).await?;
+    if let Some(err) = error {
+        ctx.send(
+            poise::CreateReply::default()
+                .content(format!("Preview failed: {}", err))
+                .ephemeral(true),
+        )
+        .await?;
+    }
+
+    loop {
+        let filter_prefix = format!("{}:", prefix);
+        let Some(press) = serenity::ComponentInteractionCollector::new(ctx)
+            .author_id(author.id)
+            .filter(move |press| press.data.custom_id.starts_with(&filter_prefix))
+            .timeout(EDITOR_TIMEOUT)
+            .await
+        else {
+            reply
+                .edit(
+                    ctx,
+                    poise::CreateReply::default()
+                        .content("Timed out, nothing was saved.")
+                        .components(vec![]),
+                )
+                .await?;
+            return Ok(());
+        };
+
+        let message = match &press.data.custom_id[prefix.len() + 1..] {
+            "reroll" => {
+                press.defer(ctx).await?;
+                voice = random_voice(&mut rng, mode, &limits);
+                let edit = serenity::EditInteractionResponse::new()
+                    .content(random_content(&voice))
+                    .components(random_components(&prefix));
+                // Drop the old voice's preview rather than leave it next to
+                // the new one.
+                match preview(ctx, &voice).await {
+                    Ok(file) => {
+                        press.edit_response(ctx, edit.new_attachment(file)).await?;
+                    }
+                    Err(err) => {
+                        press.edit_response(ctx, edit.clear_attachments()).await?;
+                        press
+                            .create_followup(
+                                ctx,
+                                serenity::CreateInteractionResponseFollowup::new()
+                                    .ephemeral(true)
+                                    .content(format!("Preview failed: {}", err)),
+                            )
+                            .await?;
+                    }
+                }
+                continue;
+            }
+            "save" => {
+                let mut voice_manager = ctx.data().voice_manager.lock().await;
+                voice_manager.set(author.id.get(), &voice).await?;
+                format!("Voice saved!\n```rust\n{:?}\n```", voice)
+            }
+            "cancel" => "Nothing was saved.".to_string(),
+            _ => continue,
+        };
+
+        press
+            .create_response(
+                ctx,
+                serenity::CreateInteractionResponse::UpdateMessage(
+                    serenity::CreateInteractionResponseMessage::new()
+                        .content(message)
+                        .components(vec![]),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+}
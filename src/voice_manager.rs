@@ -1,6 +1,10 @@
-use std::{collections::HashMap, io};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
+};
 
-use crate::dectalk::{DECtalkVoice, PAUL_VOICE};
+use crate::dectalk::{self, DECtalkVoice, DEFAULT_RATE};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tokio::fs;
 
@@ -10,35 +14,292 @@ pub enum VoiceManagerError {
     IOError(#[from] io::Error),
     #[error("Serde error: {0}")]
     SerdeError(#[from] serde_json::Error),
+    #[error("You can't save more than {0} voices")]
+    LibraryFull(usize),
+    #[error("You have no voice named \"{0}\"")]
+    NotFound(String),
+    #[error("You already have a voice named \"{0}\"")]
+    NameTaken(String),
+}
+
+/// Number of named voices a user can keep in their library
+pub const MAX_SAVED_VOICES: usize = 25;
+
+/// Number of previous voices kept per user for undo
+pub const MAX_HISTORY: usize = 20;
+
+/// A user's previous global voices, oldest first. `None` stands for not
+/// having a voice set.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VoiceHistory {
+    pub undo: Vec<Option<DECtalkVoice>>,
+    pub redo: Vec<Option<DECtalkVoice>>,
 }
 
 pub struct VoiceManager {
     pub voices: HashMap<u64, DECtalkVoice>,
+    pub rates: HashMap<u64, u16>,
+    pub libraries: HashMap<u64, BTreeMap<String, DECtalkVoice>>,
+    /// Per guild voices of users, taking precedence over their global voice
+    pub guild_voices: HashMap<u64, HashMap<u64, DECtalkVoice>>,
+    /// Voices of guild members who haven't set one
+    pub guild_defaults: HashMap<u64, DECtalkVoice>,
+    /// Guilds giving members without a voice one derived from their ID
+    pub auto_voice_guilds: HashSet<u64>,
+    pub histories: HashMap<u64, VoiceHistory>,
 }
 
 impl VoiceManager {
     pub fn new() -> Self {
         Self {
             voices: HashMap::new(),
+            rates: HashMap::new(),
+            libraries: HashMap::new(),
+            guild_voices: HashMap::new(),
+            guild_defaults: HashMap::new(),
+            auto_voice_guilds: HashSet::new(),
+            histories: HashMap::new(),
+        }
+    }
+
+    /// The voice a user speaks with: their voice for the guild, else their
+    /// global voice, else one derived from their ID if the guild enabled it,
+    /// else the guild's default voice, else Paul.
+    pub fn get(&self, guild_id: Option<u64>, id: u64) -> DECtalkVoice {
+        if let Some(voice) = guild_id
+            .and_then(|guild_id| self.get_guild_voice(guild_id, id))
+            .or_else(|| self.voices.get(&id))
+        {
+            return voice.clone();
         }
+
+        let Some(guild_id) = guild_id else {
+            return DECtalkVoice::default();
+        };
+        if self.get_auto_voice(guild_id) {
+            return dectalk::seeded_voice(id);
+        }
+        self.get_guild_default(guild_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The user's global voice, the one edited by the voice commands
+    pub fn get_user(&self, id: u64) -> &DECtalkVoice {
+        self.voices.get(&id).unwrap_or(dectalk::default_voice())
+    }
+
+    pub fn get_guild_voice(&self, guild_id: u64, id: u64) -> Option<&DECtalkVoice> {
+        self.guild_voices.get(&guild_id)?.get(&id)
     }
 
-    pub fn get(&self, id: u64) -> &DECtalkVoice {
-        self.voices.get(&id).unwrap_or(&PAUL_VOICE)
+    pub async fn set_guild_voice(
+        &mut self,
+        guild_id: u64,
+        id: u64,
+        voice: Option<&DECtalkVoice>,
+    ) -> Result<(), VoiceManagerError> {
+        let guild_voices = self.guild_voices.entry(guild_id).or_default();
+        match voice {
+            Some(voice) => guild_voices.insert(id, voice.clone()),
+            None => guild_voices.remove(&id),
+        };
+        if guild_voices.is_empty() {
+            self.guild_voices.remove(&guild_id);
+        }
+        self.save().await?;
+        Ok(())
     }
 
     pub async fn remove(&mut self, id: u64) -> Result<(), VoiceManagerError> {
+        self.record(id);
         self.voices.remove(&id);
         self.save().await?;
         Ok(())
     }
 
     pub async fn set(&mut self, id: u64, voice: &DECtalkVoice) -> Result<(), VoiceManagerError> {
+        self.record(id);
         self.voices.insert(id, voice.clone());
         self.save().await?;
         Ok(())
     }
 
+    // Remembers the current voice before it gets replaced.
+    fn record(&mut self, id: u64) {
+        let current = self.voices.get(&id).cloned();
+        let history = self.histories.entry(id).or_default();
+        history.undo.push(current);
+        if history.undo.len() > MAX_HISTORY {
+            history.undo.remove(0);
+        }
+        history.redo.clear();
+    }
+
+    // Swaps the current voice with the newest one in `from`, keeping the
+    // current voice in `to`.
+    fn step(&mut self, id: u64, redo: bool) -> bool {
+        let Some(history) = self.histories.get_mut(&id) else {
+            return false;
+        };
+        let (from, to) = if redo {
+            (&mut history.redo, &mut history.undo)
+        } else {
+            (&mut history.undo, &mut history.redo)
+        };
+        let Some(voice) = from.pop() else {
+            return false;
+        };
+
+        to.push(self.voices.get(&id).cloned());
+        match voice {
+            Some(voice) => self.voices.insert(id, voice),
+            None => self.voices.remove(&id),
+        };
+        true
+    }
+
+    /// Goes back to the previous voice, returns whether there was one.
+    pub async fn undo(&mut self, id: u64) -> Result<bool, VoiceManagerError> {
+        let changed = self.step(id, false);
+        if changed {
+            self.save().await?;
+        }
+        Ok(changed)
+    }
+
+    /// Reapplies the last undone voice, returns whether there was one.
+    pub async fn redo(&mut self, id: u64) -> Result<bool, VoiceManagerError> {
+        let changed = self.step(id, true);
+        if changed {
+            self.save().await?;
+        }
+        Ok(changed)
+    }
+
+    /// Previous voices, oldest first
+    pub fn history(&self, id: u64) -> &[Option<DECtalkVoice>] {
+        self.histories
+            .get(&id)
+            .map(|history| history.undo.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn get_rate(&self, id: u64) -> u16 {
+        self.rates.get(&id).copied().unwrap_or(DEFAULT_RATE)
+    }
+
+    pub async fn set_rate(&mut self, id: u64, rate: u16) -> Result<(), VoiceManagerError> {
+        self.rates.insert(id, rate);
+        self.save().await?;
+        Ok(())
+    }
+
+    /// Names of the user's saved voices, in alphabetical order
+    pub fn saved_names(&self, id: u64) -> Vec<&str> {
+        self.libraries
+            .get(&id)
+            .map(|library| library.keys().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn get_saved(&self, id: u64, name: &str) -> Option<&DECtalkVoice> {
+        self.libraries.get(&id)?.get(name)
+    }
+
+    /// Saves `voice` under `name`, replacing any voice already saved with
+    /// that name.
+    pub async fn save_voice(
+        &mut self,
+        id: u64,
+        name: &str,
+        voice: &DECtalkVoice,
+    ) -> Result<(), VoiceManagerError> {
+        if let Some(library) = self.libraries.get(&id)
+            && !library.contains_key(name)
+            && library.len() >= MAX_SAVED_VOICES
+        {
+            return Err(VoiceManagerError::LibraryFull(MAX_SAVED_VOICES));
+        }
+
+        self.libraries
+            .entry(id)
+            .or_default()
+            .insert(name.to_string(), voice.clone());
+        self.save().await?;
+        Ok(())
+    }
+
+    pub async fn rename_voice(
+        &mut self,
+        id: u64,
+        name: &str,
+        new_name: &str,
+    ) -> Result<(), VoiceManagerError> {
+        let not_found = || VoiceManagerError::NotFound(name.to_string());
+        let library = self.libraries.get_mut(&id).ok_or_else(not_found)?;
+        if !library.contains_key(name) {
+            return Err(not_found());
+        }
+        if name == new_name {
+            return Ok(());
+        }
+        if library.contains_key(new_name) {
+            return Err(VoiceManagerError::NameTaken(new_name.to_string()));
+        }
+
+        let voice = library.remove(name).ok_or_else(not_found)?;
+        library.insert(new_name.to_string(), voice);
+        self.save().await?;
+        Ok(())
+    }
+
+    pub async fn delete_voice(&mut self, id: u64, name: &str) -> Result<(), VoiceManagerError> {
+        let not_found = || VoiceManagerError::NotFound(name.to_string());
+        let library = self.libraries.get_mut(&id).ok_or_else(not_found)?;
+        library.remove(name).ok_or_else(not_found)?;
+        if library.is_empty() {
+            self.libraries.remove(&id);
+        }
+        self.save().await?;
+        Ok(())
+    }
+
+    pub fn get_guild_default(&self, guild_id: u64) -> Option<&DECtalkVoice> {
+        self.guild_defaults.get(&guild_id)
+    }
+
+    pub async fn set_guild_default(
+        &mut self,
+        guild_id: u64,
+        voice: Option<&DECtalkVoice>,
+    ) -> Result<(), VoiceManagerError> {
+        match voice {
+            Some(voice) => self.guild_defaults.insert(guild_id, voice.clone()),
+            None => self.guild_defaults.remove(&guild_id),
+        };
+        self.save().await?;
+        Ok(())
+    }
+
+    pub fn get_auto_voice(&self, guild_id: u64) -> bool {
+        self.auto_voice_guilds.contains(&guild_id)
+    }
+
+    pub async fn set_auto_voice(
+        &mut self,
+        guild_id: u64,
+        enabled: bool,
+    ) -> Result<(), VoiceManagerError> {
+        if enabled {
+            self.auto_voice_guilds.insert(guild_id);
+        } else {
+            self.auto_voice_guilds.remove(&guild_id);
+        }
+        self.save().await?;
+        Ok(())
+    }
+
     pub async fn can_load(&self) -> bool {
         fs::metadata("data/voices.json").await.is_ok()
     }
@@ -46,12 +307,51 @@ impl VoiceManager {
     pub async fn load(&mut self) -> Result<(), VoiceManagerError> {
         let voices_string = fs::read_to_string("data/voices.json").await?;
         self.voices = serde_json::from_str(&voices_string)?;
+
+        // Older data directories only have voices.
+        if fs::metadata("data/rates.json").await.is_ok() {
+            let rates_string = fs::read_to_string("data/rates.json").await?;
+            self.rates = serde_json::from_str(&rates_string)?;
+        }
+        if fs::metadata("data/library.json").await.is_ok() {
+            let libraries_string = fs::read_to_string("data/library.json").await?;
+            self.libraries = serde_json::from_str(&libraries_string)?;
+        }
+        if fs::metadata("data/guild_voices.json").await.is_ok() {
+            let guild_voices_string = fs::read_to_string("data/guild_voices.json").await?;
+            self.guild_voices = serde_json::from_str(&guild_voices_string)?;
+        }
+        if fs::metadata("data/guild_default_voices.json").await.is_ok() {
+            let guild_defaults_string =
+                fs::read_to_string("data/guild_default_voices.json").await?;
+            self.guild_defaults = serde_json::from_str(&guild_defaults_string)?;
+        }
+        if fs::metadata("data/auto_voice_guilds.json").await.is_ok() {
+            let auto_voice_string = fs::read_to_string("data/auto_voice_guilds.json").await?;
+            self.auto_voice_guilds = serde_json::from_str(&auto_voice_string)?;
+        }
+        if fs::metadata("data/voice_history.json").await.is_ok() {
+            let histories_string = fs::read_to_string("data/voice_history.json").await?;
+            self.histories = serde_json::from_str(&histories_string)?;
+        }
         Ok(())
     }
 
     pub async fn save(&self) -> Result<(), VoiceManagerError> {
         let voices_string = serde_json::to_string(&self.voices)?;
         fs::write("data/voices.json", voices_string).await?;
+        let rates_string = serde_json::to_string(&self.rates)?;
+        fs::write("data/rates.json", rates_string).await?;
+        let libraries_string = serde_json::to_string(&self.libraries)?;
+        fs::write("data/library.json", libraries_string).await?;
+        let guild_voices_string = serde_json::to_string(&self.guild_voices)?;
+        fs::write("data/guild_voices.json", guild_voices_string).await?;
+        let guild_defaults_string = serde_json::to_string(&self.guild_defaults)?;
+        fs::write("data/guild_default_voices.json", guild_defaults_string).await?;
+        let auto_voice_string = serde_json::to_string(&self.auto_voice_guilds)?;
+        fs::write("data/auto_voice_guilds.json", auto_voice_string).await?;
+        let histories_string = serde_json::to_string(&self.histories)?;
+        fs::write("data/voice_history.json", histories_string).await?;
         Ok(())
     }
 }